@@ -0,0 +1,88 @@
+//! Benchmarks of equation evaluation.
+//!
+//! Run with `cargo bench`.
+//!
+//! Compares the compiled [`fitme::expr::v1::Eq`] against binding the `meval` expression on each
+//! row (the previous implementation), on an iris sized dataset and a million row dataset.
+use fitme::{expr::v1::Eq, Data, Equation, Headers};
+use std::{hint::black_box, time::Instant};
+
+const EXPR: &str = "a * petalLength + b * sepalWidth + c * petalWidth + d";
+const PARAMS: [f64; 4] = [0.711, 0.654, -0.562, 1.845];
+
+fn main() {
+    for rows in [150, 1_000_000] {
+        let data = dataset(rows);
+        let eq = Eq::parse(EXPR, data.headers()).unwrap();
+
+        let legacy = time(|| legacy_eval(&data));
+        let compiled = time(|| compiled_eval(&eq, &data));
+        let batch = time(|| batch_eval(&eq, &data));
+
+        println!(
+            "{rows:>9} rows :: rebind per row {:>12.3?} :: compiled {:>12.3?} ({:.1}x) :: batch {:>12.3?} ({:.1}x)",
+            legacy,
+            compiled,
+            legacy.as_secs_f64() / compiled.as_secs_f64(),
+            batch,
+            legacy.as_secs_f64() / batch.as_secs_f64(),
+        );
+    }
+}
+
+fn dataset(rows: usize) -> Data {
+    let hdrs = Headers::from_iter(["sepalLength", "sepalWidth", "petalLength", "petalWidth"]);
+    let mut rng = fastrand::Rng::with_seed(42);
+    let data = (0..rows).map(|_| [0; 4].map(|_| rng.f64() * 8.0));
+    Data::new(hdrs, data).unwrap()
+}
+
+/// Time the function, taking the best of a few runs.
+fn time<F: FnMut() -> f64>(mut f: F) -> std::time::Duration {
+    (0..5)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .expect("at least one run")
+}
+
+fn compiled_eval(eq: &Eq, data: &Data) -> f64 {
+    data.rows().map(|row| eq.solve(&PARAMS, row).unwrap()).sum()
+}
+
+fn batch_eval(eq: &Eq, data: &Data) -> f64 {
+    let mut out = vec![0.0; data.len()];
+    eq.solve_batch(&PARAMS, data, &mut out).unwrap();
+    out.into_iter().sum()
+}
+
+fn legacy_eval(data: &Data) -> f64 {
+    let expr = EXPR.parse::<meval::Expr>().unwrap();
+    let names = [
+        "a",
+        "b",
+        "c",
+        "d",
+        "petalLength",
+        "petalWidth",
+        "sepalWidth",
+    ];
+    let cols = ["petalLength", "petalWidth", "sepalWidth"].map(|c| data.headers().find(c).unwrap());
+
+    data.rows()
+        .map(|row| {
+            let f = expr
+                .clone()
+                .bindn_with_context(meval::Context::new(), &names)
+                .unwrap();
+            let mut inputs = PARAMS.to_vec();
+            for c in cols {
+                inputs.push(row.get_num(c).unwrap().unwrap());
+            }
+            f(&inputs)
+        })
+        .sum()
+}
@@ -1,21 +1,46 @@
 use super::*;
 
+/// The cell values which are missing, if none are specified.
+pub const DEFAULT_NA: [&str; 4] = ["", "NA", "NaN", "null"];
+
 /// Input data headers representation.
-pub struct Headers(Vec<String>);
+///
+/// Headers of [`Data`] also record which columns are text, and the levels of categorical columns.
+pub struct Headers {
+    names: Vec<String>,
+    /// Whether every cell of each column is text.
+    text: Vec<bool>,
+    /// The levels of each column, empty if the column is not categorical.
+    levels: Vec<Vec<String>>,
+}
 
 /// Input data representation.
 ///
-/// Input data is represented as a set of text headers and _rows_ of numbers.
+/// Input data is represented as a set of text headers and _columns_ of numbers.
+/// Columns where every cell is a number are stored contiguously, any column containing text is
+/// kept separately.
 pub struct Data {
     cols: Headers,
-    rows: Vec<Vec<Cell>>,
+    len: usize,
+    data: Vec<Column>,
 }
 
+/// A single data cell.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Cell {
+    /// A number.
     Num(f64),
+    /// Text which is not a number.
     Txt(String),
 }
 
+enum Column {
+    /// Every cell is a number.
+    Num(Vec<f64>),
+    /// At least one cell is text.
+    Txt(Vec<Cell>),
+}
+
 macro_rules! cell_impl {
     ($([$t:ty : $v:ident $f:path])*) => {
         $(
@@ -40,12 +65,35 @@ impl Headers {
     ///
     /// This is the same as the number of columns.
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.names.len()
     }
 
     /// Returns true if there are no headers.
     pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.names.is_empty()
+    }
+
+    /// Iterate over the header names.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &str> {
+        self.names.iter().map(|x| x.as_str())
+    }
+
+    /// The levels of the categorical column at the index, the first being the reference level.
+    ///
+    /// Columns are made categorical with [`Data::set_categorical`] or [`Data::set_levels`].
+    /// Returns `None` if the column is not categorical, or headers are not from [`Data`].
+    pub fn levels(&self, colidx: usize) -> Option<&[String]> {
+        self.levels
+            .get(colidx)
+            .filter(|x| !x.is_empty())
+            .map(Vec::as_slice)
+    }
+
+    /// Is every cell of the column at the index text?
+    ///
+    /// Returns `false` if the headers are not from [`Data`].
+    pub fn is_text(&self, colidx: usize) -> bool {
+        self.text.get(colidx).copied().unwrap_or_default()
     }
 
     /// Find the column which matches the string `s`.
@@ -68,7 +116,7 @@ impl Headers {
     where
         P: Fn(&str) -> bool,
     {
-        self.0
+        self.names
             .iter()
             .enumerate()
             .find_map(|(i, x)| predicate(x).then_some(i))
@@ -78,14 +126,14 @@ impl Headers {
     pub fn fuzzy_match(&self, s: &str) -> impl Iterator<Item = String> + '_ {
         let mut eng = simsearch::SimSearch::new();
 
-        self.0
+        self.names
             .iter()
             .enumerate()
             .for_each(|(i, x)| eng.insert(i, x));
 
         let r = eng.search(s);
 
-        r.into_iter().filter_map(|i| self.0.get(i)).map(|x| {
+        r.into_iter().filter_map(|i| self.names.get(i)).map(|x| {
             let mut x = x.clone();
             x.retain(|x| !x.is_whitespace());
             x
@@ -95,17 +143,23 @@ impl Headers {
 
 impl<T: AsRef<str>> FromIterator<T> for Headers {
     fn from_iter<I: IntoIterator<Item = T>>(i: I) -> Self {
-        Headers(
-            i.into_iter()
-                .map(|t| t.as_ref().trim().to_string())
-                .collect(),
-        )
+        let names = i
+            .into_iter()
+            .map(|t| t.as_ref().trim().to_string())
+            .collect::<Vec<_>>();
+        let text = vec![false; names.len()];
+        let levels = vec![Vec::new(); names.len()];
+        Headers {
+            names,
+            text,
+            levels,
+        }
     }
 }
 
 impl Data {
     /// Build the input data from the headers and numeric numbers.
-    pub fn new<D, R, C>(headers: Headers, data: D) -> Result<Self>
+    pub fn new<D, R, C>(mut headers: Headers, data: D) -> Result<Self>
     where
         D: IntoIterator<Item = R>,
         R: IntoIterator<Item = C>,
@@ -116,32 +170,48 @@ impl Data {
         let d = data.into_iter();
 
         let (l, u) = d.size_hint();
-        let mut rows = Vec::with_capacity(u.unwrap_or(l));
+        let mut cols: Vec<Vec<Cell>> = (0..headers_len)
+            .map(|_| Vec::with_capacity(u.unwrap_or(l)))
+            .collect();
+        let mut len = 0;
 
         for (i, row) in d.into_iter().enumerate() {
-            let row = row.into_iter().map(Into::into).collect::<Vec<_>>();
+            let mut n = 0;
+            for cell in row {
+                ensure!(
+                    n < headers_len,
+                    "row index {} does not have the same length as the headers",
+                    i
+                );
+                cols[n].push(cell.into());
+                n += 1;
+            }
             ensure!(
-                headers_len == row.len(),
+                headers_len == n,
                 "row index {} does not have the same length as the headers",
                 i
             );
-            rows.push(row);
+            len += 1;
         }
 
+        let data = cols.into_iter().map(Column::from_cells).collect::<Vec<_>>();
+        headers.text = data.iter().map(Column::is_text).collect();
+
         Ok(Self {
             cols: headers,
-            rows,
+            len,
+            data,
         })
     }
 
     /// Returns the length of the number of observation rows.
     pub fn len(&self) -> usize {
-        self.rows.len()
+        self.len
     }
 
     /// Returns if there is no **data rows**. (There may still be headers).
     pub fn is_empty(&self) -> bool {
-        self.rows.is_empty()
+        self.len == 0
     }
 
     /// The set of headers.
@@ -150,21 +220,204 @@ impl Data {
     }
 
     /// Return an iterator of [`DataRow`].
-    pub fn rows(&self) -> impl ExactSizeIterator<Item = DataRow> {
-        self.rows.iter().enumerate().map(|(idx, vals)| DataRow {
-            idx,
-            vals,
-            hdrs: &self.cols,
+    pub fn rows(&self) -> impl ExactSizeIterator<Item = DataRow<'_>> {
+        (0..self.len).map(|idx| DataRow { idx, data: self })
+    }
+
+    /// Get the numeric values of the column at the index.
+    ///
+    /// If the column contains any text cells, a location error of the first text cell is
+    /// returned.
+    pub fn num_col(&self, colidx: usize) -> Option<Result<&[f64]>> {
+        self.data.get(colidx).map(|c| match c {
+            Column::Num(xs) => Ok(xs.as_slice()),
+            Column::Txt(cells) => {
+                let (idx, x) = cells
+                    .iter()
+                    .enumerate()
+                    .find_map(|(i, c)| match c {
+                        Cell::Txt(x) => Some((i, x)),
+                        Cell::Num(_) => None,
+                    })
+                    .expect("text column has at least one text cell");
+                Err(num_parse_err(x, colidx, idx))
+            }
         })
     }
+
+    /// A new data set of the rows at the indices `rows`, in that order.
+    ///
+    /// Rows can be repeated. The headers keep the levels of this data set's categorical columns.
+    ///
+    /// # Panics
+    /// Panics if a row index is out of bounds.
+    pub fn select(&self, rows: &[usize]) -> Data {
+        let data: Vec<_> = self
+            .data
+            .iter()
+            .map(|c| match c {
+                Column::Num(xs) => Column::Num(rows.iter().map(|&i| xs[i]).collect()),
+                // a subset of a text column may be all numbers
+                Column::Txt(cells) => {
+                    Column::from_cells(rows.iter().map(|&i| cells[i].clone()).collect())
+                }
+            })
+            .collect();
+
+        Data {
+            cols: Headers {
+                names: self.cols.names.clone(),
+                text: data.iter().map(Column::is_text).collect(),
+                levels: self.cols.levels.clone(),
+            },
+            len: rows.len(),
+            data,
+        }
+    }
+
+    /// Partition the row indices by the distinct values of the column at the index.
+    ///
+    /// Groups are in the order each value first appears, numbers are keyed by their formatted
+    /// value.
+    pub fn groups(&self, colidx: usize) -> Option<Vec<(String, Vec<usize>)>> {
+        let col = self.data.get(colidx)?;
+        let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+        for i in 0..self.len {
+            let key = match col {
+                Column::Num(xs) => xs[i].to_string(),
+                Column::Txt(cells) => match &cells[i] {
+                    Cell::Num(x) => x.to_string(),
+                    Cell::Txt(x) => x.clone(),
+                },
+            };
+            match groups.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, rows)) => rows.push(i),
+                None => groups.push((key, vec![i])),
+            }
+        }
+        Some(groups)
+    }
+
+    /// A new data set of the rows for which `keep` returns true.
+    ///
+    /// Like [`Data::select`], categorical columns keep their levels.
+    pub fn filter<F>(&self, mut keep: F) -> Result<Data>
+    where
+        F: FnMut(DataRow) -> Result<bool>,
+    {
+        let mut rows = Vec::with_capacity(self.len);
+        for row in self.rows() {
+            if keep(row)? {
+                rows.push(row.idx);
+            }
+        }
+
+        Ok(self.select(&rows))
+    }
+
+    /// Append a column of numbers.
+    ///
+    /// # Panics
+    /// Panics if `xs` is not the same length as the data.
+    pub fn push_num_col(&mut self, name: &str, xs: Vec<f64>) {
+        assert_eq!(xs.len(), self.len, "column length must match the data");
+        self.cols.names.push(name.to_string());
+        self.cols.text.push(false);
+        self.cols.levels.push(Vec::new());
+        self.data.push(Column::Num(xs));
+    }
+
+    /// Replace the column at the index with numbers.
+    ///
+    /// # Panics
+    /// Panics if the column index is out of bounds, or `xs` is not the same length as the data.
+    pub fn set_num_col(&mut self, colidx: usize, xs: Vec<f64>) {
+        assert_eq!(xs.len(), self.len, "column length must match the data");
+        self.data[colidx] = Column::Num(xs);
+        self.cols.text[colidx] = false;
+        self.cols.levels[colidx].clear();
+    }
+
+    /// Make the column at the index categorical, with its sorted distinct values as the levels.
+    ///
+    /// Every cell must be text.
+    ///
+    /// # Panics
+    /// Panics if the column index is out of bounds.
+    pub fn set_categorical(&mut self, colidx: usize) -> Result<()> {
+        let mut levels = self
+            .rows()
+            .filter_map(|r| r.get_txt(colidx))
+            .collect::<Vec<_>>();
+        levels.sort_unstable();
+        levels.dedup();
+        let levels = levels.into_iter().map(String::from).collect();
+        self.set_levels(colidx, levels)
+    }
+
+    /// Make the column at the index categorical with the `levels`, such as the levels of a fit.
+    ///
+    /// Every cell must be text, and one of the levels.
+    ///
+    /// # Panics
+    /// Panics if the column index is out of bounds.
+    pub fn set_levels(&mut self, colidx: usize, levels: Vec<String>) -> Result<()> {
+        let name = &self.cols.names[colidx];
+        for row in self.rows() {
+            let err = match row.get_txt(colidx) {
+                Some(x) if levels.iter().any(|l| l == x) => continue,
+                Some(x) => miette!(
+                    "'{}' is not a level of categorical column '{}', expecting one of: {}",
+                    x,
+                    name,
+                    levels.join(", ")
+                ),
+                None => miette!("categorical column '{}' must be text", name),
+            };
+            return Err(err).wrap_err(format!("in row index {}", row.idx + 1));
+        }
+
+        self.cols.levels[colidx] = levels;
+        Ok(())
+    }
+}
+
+impl Column {
+    fn from_cells(cells: Vec<Cell>) -> Self {
+        if cells.iter().all(|c| matches!(c, Cell::Num(_))) {
+            Column::Num(
+                cells
+                    .into_iter()
+                    .map(|c| match c {
+                        Cell::Num(x) => x,
+                        Cell::Txt(_) => unreachable!("all cells are numbers"),
+                    })
+                    .collect(),
+            )
+        } else {
+            Column::Txt(cells)
+        }
+    }
+
+    fn is_text(&self) -> bool {
+        match self {
+            Column::Num(_) => false,
+            Column::Txt(cells) => cells.iter().all(|c| matches!(c, Cell::Txt(_))),
+        }
+    }
+}
+
+fn num_parse_err(x: &str, colidx: usize, rowidx: usize) -> miette::Report {
+    miette!("failed to parse '{}' as number", x)
+        .wrap_err(format!("in column index {colidx}"))
+        .wrap_err(format!("in row index {}", rowidx + 1))
 }
 
 /// A single row [`Data`].
 #[derive(Copy, Clone)]
 pub struct DataRow<'a> {
     idx: usize,
-    vals: &'a [Cell],
-    hdrs: &'a Headers,
+    data: &'a Data,
 }
 
 impl<'a> DataRow<'a> {
@@ -172,28 +425,65 @@ impl<'a> DataRow<'a> {
     ///
     /// If the cell is not a number, a location error is returned.
     pub fn get_num(&self, colidx: usize) -> Option<Result<f64>> {
-        self.vals.get(colidx).map(|c| match c {
-            Cell::Num(x) => Ok(*x),
-            Cell::Txt(x) => Err(miette!("failed to parse '{}' as number", x))
-                .wrap_err_with(|| format!("in column index {colidx}"))
-                .wrap_err_with(|| format!("in row index {}", self.idx + 1)),
+        self.data.data.get(colidx).map(|c| match c {
+            Column::Num(xs) => Ok(xs[self.idx]),
+            Column::Txt(cells) => match &cells[self.idx] {
+                Cell::Num(x) => Ok(*x),
+                Cell::Txt(x) => Err(num_parse_err(x, colidx, self.idx)),
+            },
         })
     }
 
+    /// Get the cell at the column index.
+    pub fn get(&self, colidx: usize) -> Option<Cell> {
+        self.data.data.get(colidx).map(|c| match c {
+            Column::Num(xs) => Cell::Num(xs[self.idx]),
+            Column::Txt(cells) => cells[self.idx].clone(),
+        })
+    }
+
+    /// Get the text at the column index.
+    ///
+    /// Returns `None` if the cell is a number or the column is out of bounds.
+    pub fn get_txt(&self, colidx: usize) -> Option<&'a str> {
+        match self.data.data.get(colidx)? {
+            Column::Num(_) => None,
+            Column::Txt(cells) => match &cells[self.idx] {
+                Cell::Num(_) => None,
+                Cell::Txt(x) => Some(x),
+            },
+        }
+    }
+
+    /// Is the cell at the column index missing?
+    ///
+    /// A cell is missing if it is `NaN`, or text matching one of the `na` values (ignoring
+    /// surrounding whitespace).
+    pub fn is_missing(&self, colidx: usize, na: &[impl AsRef<str>]) -> bool {
+        match self.get_txt(colidx) {
+            Some(x) => na.iter().any(|n| n.as_ref() == x.trim()),
+            None => self
+                .get_num(colidx)
+                .and_then(Result::ok)
+                .is_some_and(f64::is_nan),
+        }
+    }
+
     /// The row index.
     pub fn idx(&self) -> usize {
         self.idx
     }
 
     /// The [`Data`] headers.
-    pub fn headers(&self) -> &Headers {
-        self.hdrs
+    pub fn headers(&self) -> &'a Headers {
+        &self.data.cols
     }
 }
 
 pub struct CsvReader {
     rdr: csv::Reader<Box<dyn std::io::Read>>,
     cols: Option<Headers>,
+    no_header: bool,
 }
 
 impl CsvReader {
@@ -201,10 +491,46 @@ impl CsvReader {
         Self {
             rdr: csv::Reader::from_reader(Box::new(rdr)),
             cols: None,
+            no_header: false,
         }
     }
 
+    /// Read a CSV formatted with the `dialect`, skipping its leading lines.
+    pub fn with_dialect<R: std::io::BufRead + 'static>(
+        mut rdr: R,
+        dialect: &Dialect,
+    ) -> Result<Self> {
+        let mut line = Vec::new();
+        for i in 0..dialect.skip_rows {
+            line.clear();
+            let n = rdr
+                .read_until(b'\n', &mut line)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to skip line {}", i + 1))?;
+            ensure!(
+                n > 0,
+                "cannot skip {} rows, the input has {} lines",
+                dialect.skip_rows,
+                i
+            );
+        }
+
+        let rdr = csv::ReaderBuilder::new()
+            .delimiter(dialect.delimiter)
+            .quote(dialect.quote)
+            .comment(dialect.comment)
+            .has_headers(!dialect.no_header)
+            .from_reader(Box::new(rdr) as Box<dyn std::io::Read>);
+
+        Ok(Self {
+            rdr,
+            cols: None,
+            no_header: dialect.no_header,
+        })
+    }
+
     fn read_headers(&mut self) -> Result<()> {
+        // without a header row, this is the first record which is still read as data
         let hdrs = self
             .rdr
             .headers()
@@ -213,7 +539,10 @@ impl CsvReader {
 
         ensure!(!hdrs.is_empty(), "headers row is empty");
 
-        self.cols = Some(hdrs.iter().collect());
+        self.cols = Some(match self.no_header {
+            true => (0..hdrs.len()).map(|i| format!("c{i}")).collect(),
+            false => hdrs.iter().collect(),
+        });
         Ok(())
     }
 
@@ -303,6 +632,143 @@ mod tests {
     use fastrand::*;
     use std::iter::*;
 
+    /// A numeric column, and a column of text and numbers.
+    fn mixed() -> Data {
+        Data::new(
+            Headers::from_iter(["a", "b"]),
+            vec![
+                vec![Cell::from(1.0), Cell::from("foo")],
+                vec![Cell::from(2.0), Cell::from(3.0)],
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn numeric_and_text_columns() {
+        let d = mixed();
+
+        assert_eq!(d.len(), 2);
+        assert_eq!(d.num_col(0).unwrap().unwrap(), &[1.0, 2.0]);
+        assert_eq!(
+            d.num_col(1).unwrap().unwrap_err().to_string(),
+            "in row index 1"
+        );
+        assert!(d.num_col(2).is_none());
+
+        let r = d.rows().nth(1).unwrap();
+        assert_eq!(r.get_num(1).unwrap().unwrap(), 3.0);
+        assert!(d.rows().next().unwrap().get_num(1).unwrap().is_err());
+
+        assert!(Data::new(Headers::from_iter(["a"]), vec![vec![1.0, 2.0]]).is_err());
+        assert!(Data::new(Headers::from_iter(["a", "b"]), vec![vec![1.0]]).is_err());
+    }
+
+    #[test]
+    fn select_rows() {
+        let d = mixed();
+
+        let s = d.select(&[1, 1]);
+        assert_eq!(s.len(), 2);
+        assert_eq!(s.num_col(1).unwrap().unwrap(), &[3.0, 3.0]);
+
+        // a subset of a mixed column may be all text
+        assert!(!d.headers().is_text(1));
+        let s = d.select(&[0, 0]);
+        assert_eq!(s.rows().next().unwrap().get_txt(1), Some("foo"));
+        assert!(s.headers().is_text(1));
+
+        let mut s = d.select(&[0]);
+        assert!(s.num_col(1).unwrap().is_err());
+        s.set_num_col(1, vec![5.0]);
+        assert_eq!(s.num_col(1).unwrap().unwrap(), &[5.0]);
+        assert!(!s.headers().is_text(1));
+    }
+
+    #[test]
+    fn groups() {
+        let d = mixed();
+
+        let g = d.select(&[0, 1, 0]).groups(1).unwrap();
+        assert_eq!(
+            g,
+            vec![("foo".to_string(), vec![0, 2]), ("3".to_string(), vec![1])]
+        );
+        assert!(d.groups(2).is_none());
+    }
+
+    #[test]
+    fn categorical_levels() {
+        // only columns where every cell is text can be categorical
+        assert!(mixed().set_categorical(1).is_err());
+
+        let mut d = Data::new(
+            Headers::from_iter(["a", "b"]),
+            [("b", 1.0), ("a", 2.0), ("b", 3.0)].map(|(a, b)| [Cell::from(a), Cell::from(b)]),
+        )
+        .unwrap();
+        assert!(d.headers().is_text(0));
+        assert!(d.headers().levels(0).is_none());
+        d.set_categorical(0).unwrap();
+        assert_eq!(d.headers().levels(0).unwrap(), &["a", "b"]);
+        assert!(d.headers().levels(1).is_none());
+        assert!(Headers::from_iter(["a"]).levels(0).is_none());
+
+        // subsets keep the levels
+        assert_eq!(d.select(&[0]).headers().levels(0).unwrap(), &["a", "b"]);
+        let mut s = d.select(&[0, 1, 2]);
+        s.set_levels(0, vec!["b".into(), "a".into(), "c".into()])
+            .unwrap();
+        assert_eq!(s.headers().levels(0).unwrap(), &["b", "a", "c"]);
+        let e = d.set_levels(0, vec!["a".into()]).unwrap_err();
+        assert_eq!(e.to_string(), "in row index 1");
+        assert_eq!(
+            e.root_cause().to_string(),
+            "'b' is not a level of categorical column 'a', expecting one of: a"
+        );
+    }
+
+    #[test]
+    fn missing_values() {
+        let d = Data::new(
+            Headers::from_iter(["a", "b"]),
+            [("NA", 1.0), (" ", f64::NAN), ("x", 2.0)].map(|(a, b)| [Cell::from(a), Cell::from(b)]),
+        )
+        .unwrap();
+        let missing = d
+            .rows()
+            .map(|r| [r.is_missing(0, &DEFAULT_NA), r.is_missing(1, &DEFAULT_NA)])
+            .collect::<Vec<_>>();
+        assert_eq!(missing, [[true, false], [true, true], [false, false]]);
+    }
+
+    #[test]
+    fn csv_dialect() {
+        let csv = "generated\n# comment\n1\t'a\tb'\n2\tc\n";
+        let dialect = Dialect {
+            delimiter: b'\t',
+            quote: b'\'',
+            comment: Some(b'#'),
+            no_header: true,
+            skip_rows: 1,
+        };
+        let data = CsvReader::with_dialect(std::io::Cursor::new(csv), &dialect)
+            .unwrap()
+            .into_data()
+            .unwrap();
+
+        assert_eq!(data.headers().iter().collect::<Vec<_>>(), ["c0", "c1"]);
+        assert_eq!(data.num_col(0).unwrap().unwrap(), &[1.0, 2.0]);
+        assert!(data.headers().is_text(1));
+        assert_eq!(data.rows().next().unwrap().get_txt(1), Some("a\tb"));
+
+        let dialect = Dialect {
+            skip_rows: 5,
+            ..dialect
+        };
+        assert!(CsvReader::with_dialect(std::io::Cursor::new(csv), &dialect).is_err());
+    }
+
     #[test]
     fn eq_testing() {
         use str_eq_ignore_case_and_ws as f;
@@ -316,7 +782,7 @@ mod tests {
         for _ in 0..10_000 {
             let a: String = repeat_with(|| char(..)).take(usize(..100)).collect();
             let b = a.chars().fold(String::new(), |mut s, c| {
-                s.extend(repeat(' ').take(usize(..2)));
+                s.extend(repeat_n(' ', usize(..2)));
                 s.push(c);
                 s
             });
@@ -0,0 +1,94 @@
+//! Columns derived from other columns.
+use super::*;
+use data::{Data, Headers};
+
+/// A derived column definition, such as `logx = ln(x)`.
+///
+/// The definition is an equation of columns, parsed with the same resolver as the fitted
+/// equation.
+pub struct Derived<E> {
+    name: String,
+    eq: E,
+}
+
+impl<E: Equation> Derived<E> {
+    /// Parse a definition of the form `name = expr` against the `columns`.
+    ///
+    /// The name must not already be a column.
+    pub fn parse(def: &str, columns: &Headers) -> Result<Self> {
+        let (name, expr) = def
+            .split_once('=')
+            .map(|(a, b)| (a.trim(), b.trim()))
+            .ok_or_else(|| miette!("expecting the form `name = expr`, found '{}'", def))?;
+        // names must be usable as equation variables
+        ensure!(
+            name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            "column name must be ASCII letters, digits or underscores, not starting with a digit, \
+             found '{}'",
+            name
+        );
+        ensure!(
+            columns.find_ignore_case_and_ws(name).is_none(),
+            "column '{}' already exists",
+            name
+        );
+
+        let eq = E::parse(expr, columns).wrap_err_with(|| format!("in definition: {def}"))?;
+        let unknown = eq.params();
+        ensure!(
+            unknown.is_empty(),
+            "unknown columns in definition of '{}': {}",
+            name,
+            unknown.join(", ")
+        );
+
+        Ok(Self {
+            name: name.to_string(),
+            eq,
+        })
+    }
+
+    /// Compute the column for every row and append it to `data`.
+    pub fn apply(&self, data: &mut Data) -> Result<()> {
+        let mut xs = vec![0.0; data.len()];
+        self.eq
+            .solve_batch(&[], data, &mut xs)
+            .ok_or_else(|| miette!("failed to compute column '{}'", self.name))?;
+        data.push_num_col(&self.name, xs);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derived_columns() {
+        let mut data = Data::new(Headers::from_iter(["x"]), [[1.0], [4.0]]).unwrap();
+
+        for def in ["sqrtx = sqrt(x)", "y=sqrtx + x"] {
+            Derived::<expr::v1::Eq>::parse(def, data.headers())
+                .unwrap()
+                .apply(&mut data)
+                .unwrap();
+        }
+
+        assert_eq!(
+            data.headers().iter().collect::<Vec<_>>(),
+            ["x", "sqrtx", "y"]
+        );
+        assert_eq!(data.num_col(2).unwrap().unwrap(), &[2.0, 6.0]);
+
+        let parse = |s: &str| Derived::<expr::v1::Eq>::parse(s, data.headers());
+        assert!(parse("ln(x)").is_err());
+        assert!(parse("X = ln(x)").is_err());
+        assert!(parse("z = k * x").is_err());
+        assert!(parse("a b = x").is_err());
+        assert!(parse("é = x").is_err());
+        assert!(parse("2x = 2 * x").is_err());
+        assert!(parse(" = x").is_err());
+        assert!(parse("_x2 = x").is_ok());
+    }
+}
@@ -1,7 +1,7 @@
 //! Mathematical expression parsing and evaluation.
 
 use super::*;
-use data::{DataRow, Headers};
+use data::{Data, DataRow, Headers};
 
 pub mod v1;
 
@@ -16,6 +16,30 @@ pub trait Equation: Sized {
     /// Evaluate the expression with the given set of parameters and a single data row.
     fn solve(&self, params: &[f64], row: DataRow) -> Option<f64>;
 
+    /// Evaluate the expression with the given set of parameters for every row in `data`, writing
+    /// the results into `out`.
+    ///
+    /// `out` is expected to be the same length as `data`.
+    /// The default implementation calls [`Equation::solve`] on each row, implementors are
+    /// encouraged to provide a specialised version which avoids the per-row overhead.
+    fn solve_batch(&self, params: &[f64], data: &Data, out: &mut [f64]) -> Option<()> {
+        for (y, row) in out.iter_mut().zip(data.rows()) {
+            *y = self.solve(params, row)?;
+        }
+
+        Some(())
+    }
+
+    /// Evaluate the partial derivatives of the expression with respect to each parameter, for
+    /// every row in `data`.
+    ///
+    /// `out` is row-major, with `data.len()` rows of `params_len()` derivatives.
+    /// Returns `None` if analytic derivatives are not supported (the default), in which case the
+    /// solver falls back to finite differences.
+    fn jacobian_batch(&self, _params: &[f64], _data: &Data, _out: &mut [f64]) -> Option<()> {
+        None
+    }
+
     /// Fetch the string form of the expression, if it exists.
     fn expr(&self) -> Option<String>;
 
@@ -24,6 +48,13 @@ pub trait Equation: Sized {
 
     /// Extract out the variable names.
     fn vars(&self) -> Vec<String>;
+
+    /// Extract out the names of the categorical columns, see [`Headers::levels`].
+    ///
+    /// The default implementation has no categorical columns.
+    fn categorical(&self) -> Vec<String> {
+        Vec::new()
+    }
 }
 
 #[cfg(test)]
@@ -41,4 +72,28 @@ mod tests {
 
         test::<v1::Eq>();
     }
+
+    #[test]
+    fn batch_matches_solve() {
+        fn test<E: Equation>() {
+            let data = Data::new(
+                Headers::from_iter(["x", "y"]),
+                vec![vec![1.0, 2.0], vec![-0.5, 3.0], vec![4.0, 0.25]],
+            )
+            .unwrap();
+            let e = E::parse("a * x ^ 2 + b * y - exp(x)", data.headers()).unwrap();
+            let params = [1.5, -0.3];
+
+            let mut out = vec![0.0; data.len()];
+            e.solve_batch(&params, &data, &mut out).unwrap();
+
+            let exp = data
+                .rows()
+                .map(|r| e.solve(&params, r).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(out, exp);
+        }
+
+        test::<v1::Eq>();
+    }
 }
@@ -1,16 +1,23 @@
 //! Version 1 of the equation resolver.
 use super::*;
-use meval::{tokenizer::Token, ContextProvider, Expr};
+use meval::{
+    tokenizer::{Operation, Token},
+    ContextProvider, Expr,
+};
 
 /*** A note on the implementation ***
  *
- * `meval` parses an expression to obtain a token stream, however when binding, the stream is
- * consumed. Binding also requires a lifetime of the variables for the function to live, this is
- * unfortunately not available with the `Equation` trait without changing the `parse` function,
- * which I am fairly unwilling to do.
+ * `meval` parses an expression to obtain a token stream (in RPN), however binding consumes the
+ * expression and the bound function does a string lookup of each variable on _every_ call.
+ * This is far too slow when evaluating a large dataset many times over.
  *
- * Instead, the bind happens on the `solve` method, both params and variables are bound in this
- * call which solves the lifetime issue.
+ * Instead, `meval` is only used to parse and validate the expression. The RPN token stream is
+ * built into an expression tree, with variables resolved into parameter or column slots. The
+ * tree is then compiled _once_ into a small stack based program. Solving is a pure numeric
+ * evaluation of this program.
+ *
+ * Having the tree also allows symbolic differentiation with respect to each parameter, each
+ * partial derivative is compiled into its own program.
  */
 
 fn ctx() -> meval::Context<'static> {
@@ -23,16 +30,31 @@ fn ctx() -> meval::Context<'static> {
 ///
 /// Equations are expected to be the typical RHS. For instance, to solve for `y = m * x + c`, the
 /// equation to parse is `m * x + c`.
+///
+/// Categorical columns (see [`Headers::levels`]) are expanded into indicator variables. A term
+/// multiplied by a categorical column is repeated for each level except the first (reference)
+/// level, with a parameter per level. For instance, if `class` has the levels `A`, `B` and `C`,
+/// `a * class + c` is expanded to `a[B] * (class == B) + a[C] * (class == C) + c`.
 #[derive(Clone)]
 pub struct Eq {
     /// Variable (column) bindings.
     vars: Vec<(String, usize)>,
 
+    /// Categorical column bindings.
+    cats: Vec<(String, usize)>,
+
+    /// Categorical level indicators, the column index and level.
+    /// These are bound after the column variables.
+    levels: Vec<(usize, String)>,
+
     /// Unmapped variables represent the parameters to twiddle with.
     params: Vec<String>,
 
-    /// Parsed expression.
-    expr: Expr,
+    /// Compiled expression.
+    prog: Program,
+
+    /// Compiled partial derivatives with respect to each parameter.
+    derivs: Vec<Program>,
 
     /// Expression
     estr: String,
@@ -50,6 +72,7 @@ impl Equation for Eq {
         // map any *matched* variables as column variables, and
         // any *unmatched* variables as parameters
         let mut vars = Vec::new();
+        let mut cats = Vec::new();
         let mut params = Vec::new();
         for t in func.iter() {
             if let Token::Var(n) = t {
@@ -58,6 +81,7 @@ impl Equation for Eq {
                 }
 
                 match columns.find_ignore_case_and_ws(n) {
+                    Some(i) if columns.levels(i).is_some() => cats.push((n.to_string(), i)),
                     Some(i) => vars.push((n.to_string(), i)),
                     None => params.push(n.to_string()),
                 }
@@ -66,26 +90,61 @@ impl Equation for Eq {
 
         vars.sort_unstable();
         vars.dedup();
+        cats.sort_unstable();
+        cats.dedup();
         params.sort_unstable();
         params.dedup();
 
-        let x = Self {
-            vars,
-            params,
-            expr: func,
-            estr: expr.to_string(),
-        };
-
         // do a trial run to ensure that the expr could be evaluated
-        let v = x.build_inputs();
-        let _ = x
-            .expr
+        let inputs = params
+            .iter()
+            .map(String::as_str)
+            .chain(vars.iter().chain(&cats).map(|(x, _)| x.as_str()))
+            .collect::<Vec<_>>();
+        let _ = func
             .clone()
-            .bindn_with_context(ctx, &v)
+            .bindn_with_context(&ctx, &inputs)
             .into_diagnostic()
-            .wrap_err_with(|| format!("in expr: {}", x.estr))?;
+            .wrap_err_with(|| format!("in expr: {expr}"))?;
+
+        // categorical variables are bound after the column variables, then expanded out
+        let bound = vars.iter().chain(&cats).cloned().collect::<Vec<_>>();
+        let mut tree = Node::from_rpn(&func, &ctx, &params, &bound)
+            .wrap_err_with(|| format!("in expr: {expr}"))?;
+        let mut levels = Vec::new();
+        if !cats.is_empty() {
+            let mut x = Expand {
+                params: &params,
+                nvars: vars.len(),
+                cats: &cats,
+                columns,
+                expanded: Vec::new(),
+                levels: Vec::new(),
+            };
+            // the first pass finds the expanded parameters, which are sorted before the second
+            // pass builds the tree
+            x.node(&tree, None)
+                .wrap_err_with(|| format!("in expr: {expr}"))?;
+            x.expanded.sort_unstable();
+            tree = x.node(&tree, None).expect("expanded once");
+            levels = x.levels;
+            params = x.expanded;
+        }
+
+        let prog = Program::compile(&tree);
+        let derivs = (0..params.len())
+            .map(|p| Program::compile(&tree.diff(p)))
+            .collect();
 
-        Ok(x)
+        Ok(Self {
+            vars,
+            cats,
+            levels,
+            params,
+            prog,
+            derivs,
+            estr: expr.to_string(),
+        })
     }
 
     fn params_len(&self) -> usize {
@@ -93,26 +152,74 @@ impl Equation for Eq {
     }
 
     fn solve(&self, params: &[f64], row: DataRow) -> Option<f64> {
-        // build a vector of the params + variable names
-        let vars = self.build_inputs();
+        // fetch the column values for each variable
+        let mut vars = Vec::with_capacity(self.vars.len());
+        for (_, i) in &self.vars {
+            vars.push(row.get_num(*i)?.map_err(|e| eprintln!("{e}")).ok()?);
+        }
+        for (i, level) in &self.levels {
+            vars.push(indicator(row.get_txt(*i), level));
+        }
 
-        // bind the expression to the variables
-        let f = self
-            .expr
-            .clone()
-            .bindn_with_context(ctx(), &vars)
-            .map_err(|e| eprintln!("{e}"))
-            .ok()?;
+        let mut stack = Vec::with_capacity(self.prog.depth);
+        Some(self.prog.eval(params, &vars, &mut stack)) // eval the function
+    }
 
-        // build the inputs
-        let mut inputs = Vec::with_capacity(vars.len());
+    fn solve_batch(&self, params: &[f64], data: &Data, out: &mut [f64]) -> Option<()> {
+        let inds = self.indicators(data);
+        let cols = self
+            .vars
+            .iter()
+            .map(|(_, i)| data.num_col(*i)?.map_err(|e| eprintln!("{e}")).ok())
+            .chain(inds.iter().map(|x| Some(x.as_slice())))
+            .collect::<Option<Vec<_>>>()?;
 
-        inputs.extend_from_slice(params); // first, the stored params
-        for (_, i) in &self.vars {
-            inputs.push(row.get_num(*i)?.map_err(|e| eprintln!("{e}")).ok()?); // then the params
+        // scratch space is reused for every row
+        let mut vars = vec![0.0; cols.len()];
+        let mut stack = Vec::with_capacity(self.prog.depth);
+
+        for (r, y) in out.iter_mut().enumerate() {
+            for (x, col) in vars.iter_mut().zip(&cols) {
+                *x = col[r];
+            }
+
+            *y = self.prog.eval(params, &vars, &mut stack);
+        }
+
+        Some(())
+    }
+
+    fn jacobian_batch(&self, params: &[f64], data: &Data, out: &mut [f64]) -> Option<()> {
+        let inds = self.indicators(data);
+        let cols = self
+            .vars
+            .iter()
+            .map(|(_, i)| data.num_col(*i)?.map_err(|e| eprintln!("{e}")).ok())
+            .chain(inds.iter().map(|x| Some(x.as_slice())))
+            .collect::<Option<Vec<_>>>()?;
+
+        let depth = self
+            .derivs
+            .iter()
+            .map(|p| p.depth)
+            .max()
+            .unwrap_or_default();
+
+        // scratch space is reused for every row
+        let mut vars = vec![0.0; cols.len()];
+        let mut stack = Vec::with_capacity(depth);
+
+        for (r, row) in out.chunks_exact_mut(self.derivs.len()).enumerate() {
+            for (x, col) in vars.iter_mut().zip(&cols) {
+                *x = col[r];
+            }
+
+            for (d, prog) in row.iter_mut().zip(&self.derivs) {
+                *d = prog.eval(params, &vars, &mut stack);
+            }
         }
 
-        Some(f(&inputs)) // eval the function
+        Some(())
     }
 
     fn expr(&self) -> Option<String> {
@@ -126,14 +233,773 @@ impl Equation for Eq {
     fn vars(&self) -> Vec<String> {
         self.vars.iter().map(|(s, _)| s.clone()).collect()
     }
+
+    fn categorical(&self) -> Vec<String> {
+        self.cats.iter().map(|(s, _)| s.clone()).collect()
+    }
 }
 
 impl Eq {
-    fn build_inputs(&self) -> Vec<&str> {
-        self.params
+    /// The indicator column of each categorical level.
+    fn indicators(&self, data: &Data) -> Vec<Vec<f64>> {
+        self.levels
             .iter()
-            .map(String::as_str)
-            .chain(self.vars.iter().map(|(x, _)| x.as_str()))
+            .map(|(i, level)| {
+                data.rows()
+                    .map(|r| indicator(r.get_txt(*i), level))
+                    .collect()
+            })
             .collect()
     }
 }
+
+fn indicator(x: Option<&str>, level: &str) -> f64 {
+    if x == Some(level) {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Expands products with categorical variables into sums over the levels, see [`Eq`].
+struct Expand<'a> {
+    params: &'a [String],
+    /// The number of column variables, categorical variables are indexed after these.
+    nvars: usize,
+    cats: &'a [(String, usize)],
+    columns: &'a Headers,
+    /// The parameters of the expanded tree.
+    expanded: Vec<String>,
+    /// The indicator variables of the expanded tree, the column index and level.
+    levels: Vec<(usize, String)>,
+}
+
+impl Expand<'_> {
+    /// Expand the node, suffixing parameters with the categorical `level` if inside a product.
+    fn node(&mut self, node: &Node, level: Option<&str>) -> Result<Node> {
+        let node = match node {
+            Node::Num(x) => Node::Num(*x),
+            Node::Param(i) => {
+                let name = match level {
+                    Some(l) => format!("{}[{}]", self.params[*i], l),
+                    None => self.params[*i].clone(),
+                };
+                Node::Param(intern(&mut self.expanded, name))
+            }
+            Node::Var(i) => match self.cat(node) {
+                Some(c) => bail!(
+                    "categorical column '{0}' must multiply a term with parameters, eg `a * {0}`",
+                    self.cats[c].0
+                ),
+                None => Node::Var(*i),
+            },
+            Node::Bin(Bin::Mul, a, b) => match (self.cat(a), self.cat(b)) {
+                (None, Some(c)) => self.levels_of(a, c, level)?,
+                (Some(c), None) => self.levels_of(b, c, level)?,
+                _ => Node::Bin(Bin::Mul, self.boxed(a, level)?, self.boxed(b, level)?),
+            },
+            Node::Neg(a) => Node::Neg(self.boxed(a, level)?),
+            Node::Bin(op, a, b) => Node::Bin(*op, self.boxed(a, level)?, self.boxed(b, level)?),
+            Node::Fn1(op, a) => Node::Fn1(*op, self.boxed(a, level)?),
+            Node::Fn2(op, a, b) => Node::Fn2(*op, self.boxed(a, level)?, self.boxed(b, level)?),
+            Node::FnN(op, xs) => Node::FnN(
+                *op,
+                xs.iter()
+                    .map(|x| self.node(x, level))
+                    .collect::<Result<_>>()?,
+            ),
+        };
+        Ok(node)
+    }
+
+    fn boxed(&mut self, node: &Node, level: Option<&str>) -> Result<Box<Node>> {
+        self.node(node, level).map(Box::new)
+    }
+
+    /// The index into the categorical variables, if the node is one.
+    fn cat(&self, node: &Node) -> Option<usize> {
+        match node {
+            Node::Var(i) if *i >= self.nvars => Some(i - self.nvars),
+            _ => None,
+        }
+    }
+
+    /// Expand `term * cat` into the sum of `term[level] * (cat == level)` over every level except
+    /// the first (reference) level.
+    fn levels_of(&mut self, term: &Node, cat: usize, level: Option<&str>) -> Result<Node> {
+        let (name, col) = &self.cats[cat];
+        ensure!(
+            level.is_none(),
+            "products of categorical columns are not supported, found '{}'",
+            name
+        );
+        ensure!(
+            (0..self.params.len()).any(|p| term.depends(p)),
+            "categorical column '{0}' must multiply a term with parameters, eg `a * {0}`",
+            name
+        );
+        let levels = self.columns.levels(*col).expect("categorical column");
+        ensure!(
+            levels.len() > 1,
+            "categorical column '{}' must have more than one level",
+            name
+        );
+
+        let mut sum = Node::Num(0.0);
+        for l in &levels[1..] {
+            let t = self.node(term, Some(l))?;
+            let v = self.nvars + intern(&mut self.levels, (*col, l.clone()));
+            sum = add(sum, mul(t, Node::Var(v)));
+        }
+        Ok(sum)
+    }
+}
+
+/// The index of `x` in `xs`, pushing it if not found.
+fn intern<T: PartialEq>(xs: &mut Vec<T>, x: T) -> usize {
+    match xs.iter().position(|y| *y == x) {
+        Some(i) => i,
+        None => {
+            xs.push(x);
+            xs.len() - 1
+        }
+    }
+}
+
+/// An expression tree.
+#[derive(Clone, Debug)]
+enum Node {
+    Num(f64),
+    /// Index into the parameters.
+    Param(usize),
+    /// Index into the variables.
+    Var(usize),
+    Neg(Box<Node>),
+    Bin(Bin, Box<Node>, Box<Node>),
+    Fn1(Fn1, Box<Node>),
+    Fn2(Fn2, Box<Node>, Box<Node>),
+    FnN(FnN, Vec<Node>),
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Bin {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Fn1 {
+    Log,
+    Sqrt,
+    Exp,
+    Ln,
+    Abs,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Asinh,
+    Acosh,
+    Atanh,
+    Floor,
+    Ceil,
+    Round,
+    Signum,
+    /// Not user accessible, used in derivatives.
+    Trunc,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Fn2 {
+    Atan2,
+    /// Not user accessible, returns 1 if the arguments are equal, 0 otherwise.
+    Eq,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum FnN {
+    Max,
+    Min,
+}
+
+impl Node {
+    /// Build the tree from the RPN token stream.
+    ///
+    /// Variables are resolved to constants in `ctx`, then `params`, then `vars`.
+    fn from_rpn(
+        expr: &Expr,
+        ctx: &impl ContextProvider,
+        params: &[String],
+        vars: &[(String, usize)],
+    ) -> Result<Self> {
+        let mut stack: Vec<Node> = Vec::with_capacity(expr.len());
+
+        for t in expr.iter() {
+            let node = match t {
+                Token::Number(x) => Node::Num(*x),
+                Token::Var(n) => {
+                    if let Some(x) = ctx.get_var(n) {
+                        Node::Num(x)
+                    } else if let Some(i) = params.iter().position(|p| p == n) {
+                        Node::Param(i)
+                    } else if let Some(i) = vars.iter().position(|(v, _)| v == n) {
+                        Node::Var(i)
+                    } else {
+                        bail!("unknown variable '{}'", n);
+                    }
+                }
+                Token::Unary(Operation::Minus) => Node::Neg(pop_node(&mut stack)?),
+                Token::Unary(Operation::Plus) => continue, // no-op
+                Token::Binary(op) => {
+                    let op = match op {
+                        Operation::Plus => Bin::Add,
+                        Operation::Minus => Bin::Sub,
+                        Operation::Times => Bin::Mul,
+                        Operation::Div => Bin::Div,
+                        Operation::Rem => Bin::Rem,
+                        Operation::Pow => Bin::Pow,
+                    };
+                    let r = pop_node(&mut stack)?;
+                    let l = pop_node(&mut stack)?;
+                    Node::Bin(op, l, r)
+                }
+                Token::Func(n, Some(i)) => func(n, *i, &mut stack)?,
+                t => bail!("unexpected token in expression: {:?}", t),
+            };
+
+            stack.push(node);
+        }
+
+        ensure!(
+            stack.len() == 1,
+            "expression does not evaluate to a single value"
+        );
+
+        Ok(stack.pop().expect("one node"))
+    }
+
+    /// Does the tree depend on the parameter `p`?
+    fn depends(&self, p: usize) -> bool {
+        match self {
+            Node::Num(_) | Node::Var(_) => false,
+            Node::Param(i) => *i == p,
+            Node::Neg(a) | Node::Fn1(_, a) => a.depends(p),
+            Node::Bin(_, a, b) | Node::Fn2(_, a, b) => a.depends(p) || b.depends(p),
+            Node::FnN(_, xs) => xs.iter().any(|x| x.depends(p)),
+        }
+    }
+
+    /// The partial derivative of the tree with respect to parameter `p`.
+    fn diff(&self, p: usize) -> Node {
+        use Node::*;
+
+        if !self.depends(p) {
+            return Num(0.0);
+        }
+
+        match self {
+            Num(_) | Var(_) => Num(0.0),
+            Param(_) => Num(1.0), // must be p, otherwise it does not depend on p
+            Neg(a) => neg(a.diff(p)),
+            Bin(op, a, b) => {
+                let (a, b) = (a.as_ref().clone(), b.as_ref().clone());
+                let (da, db) = (a.diff(p), b.diff(p));
+                match op {
+                    self::Bin::Add => add(da, db),
+                    self::Bin::Sub => sub(da, db),
+                    self::Bin::Mul => add(mul(da, b.clone()), mul(a, db)),
+                    self::Bin::Div => sub(div(da, b.clone()), div(mul(a, db), pow(b, Num(2.0)))),
+                    self::Bin::Rem => sub(da, mul(db, fn1(self::Fn1::Trunc, div(a, b)))),
+                    self::Bin::Pow if !b.depends(p) => {
+                        mul(mul(b.clone(), pow(a, sub(b, Num(1.0)))), da)
+                    }
+                    self::Bin::Pow => mul(
+                        pow(a.clone(), b.clone()),
+                        add(mul(db, fn1(self::Fn1::Ln, a.clone())), div(mul(b, da), a)),
+                    ),
+                }
+            }
+            Fn1(f, a) => {
+                use self::Fn1::*;
+                let a = a.as_ref().clone();
+                let da = a.diff(p);
+                let sq = |x: Node| pow(x, Num(2.0));
+                match f {
+                    Log => div(da, mul(a, Num(std::f64::consts::LN_10))),
+                    Sqrt => div(da, mul(Num(2.0), fn1(Sqrt, a))),
+                    Exp => mul(fn1(Exp, a), da),
+                    Ln => div(da, a),
+                    Abs => mul(fn1(Signum, a), da),
+                    Sin => mul(fn1(Cos, a), da),
+                    Cos => neg(mul(fn1(Sin, a), da)),
+                    Tan => div(da, sq(fn1(Cos, a))),
+                    Asin => div(da, fn1(Sqrt, sub(Num(1.0), sq(a)))),
+                    Acos => neg(div(da, fn1(Sqrt, sub(Num(1.0), sq(a))))),
+                    Atan => div(da, add(Num(1.0), sq(a))),
+                    Sinh => mul(fn1(Cosh, a), da),
+                    Cosh => mul(fn1(Sinh, a), da),
+                    Tanh => mul(sub(Num(1.0), sq(fn1(Tanh, a))), da),
+                    Asinh => div(da, fn1(Sqrt, add(sq(a), Num(1.0)))),
+                    Acosh => div(da, fn1(Sqrt, sub(sq(a), Num(1.0)))),
+                    Atanh => div(da, sub(Num(1.0), sq(a))),
+                    Floor | Ceil | Round | Signum | Trunc => Num(0.0), // piecewise constant
+                }
+            }
+            Fn2(f, a, b) => {
+                let (a, b) = (a.as_ref().clone(), b.as_ref().clone());
+                match f {
+                    // d/dp atan2(a, b) = (b * da - a * db) / (a^2 + b^2)
+                    self::Fn2::Atan2 => div(
+                        sub(mul(b.clone(), a.diff(p)), mul(a.clone(), b.diff(p))),
+                        add(pow(a, Num(2.0)), pow(b, Num(2.0))),
+                    ),
+                    self::Fn2::Eq => Num(0.0),
+                }
+            }
+            // the derivative follows the argument which is selected
+            FnN(_, xs) => xs.iter().fold(Num(0.0), |acc, x| {
+                add(
+                    acc,
+                    mul(fn2(self::Fn2::Eq, x.clone(), self.clone()), x.diff(p)),
+                )
+            }),
+        }
+    }
+}
+
+fn pop_node(stack: &mut Vec<Node>) -> Result<Box<Node>> {
+    stack
+        .pop()
+        .map(Box::new)
+        .ok_or_else(|| miette!("expression stack underflow"))
+}
+
+// Node constructors which do some simple folding of constants and identities.
+// These keep the derivative trees from growing needlessly.
+
+fn neg(a: Node) -> Node {
+    match a {
+        Node::Num(x) => Node::Num(-x),
+        Node::Neg(x) => *x,
+        a => Node::Neg(Box::new(a)),
+    }
+}
+
+fn add(a: Node, b: Node) -> Node {
+    match (a, b) {
+        (Node::Num(x), Node::Num(y)) => Node::Num(x + y),
+        (Node::Num(0.0), b) => b,
+        (a, Node::Num(0.0)) => a,
+        (a, b) => Node::Bin(Bin::Add, Box::new(a), Box::new(b)),
+    }
+}
+
+fn sub(a: Node, b: Node) -> Node {
+    match (a, b) {
+        (Node::Num(x), Node::Num(y)) => Node::Num(x - y),
+        (Node::Num(0.0), b) => neg(b),
+        (a, Node::Num(0.0)) => a,
+        (a, b) => Node::Bin(Bin::Sub, Box::new(a), Box::new(b)),
+    }
+}
+
+fn mul(a: Node, b: Node) -> Node {
+    match (a, b) {
+        (Node::Num(x), Node::Num(y)) => Node::Num(x * y),
+        (Node::Num(0.0), _) | (_, Node::Num(0.0)) => Node::Num(0.0),
+        (Node::Num(1.0), b) => b,
+        (a, Node::Num(1.0)) => a,
+        (a, b) => Node::Bin(Bin::Mul, Box::new(a), Box::new(b)),
+    }
+}
+
+fn div(a: Node, b: Node) -> Node {
+    match (a, b) {
+        (Node::Num(x), Node::Num(y)) => Node::Num(x / y),
+        (Node::Num(0.0), _) => Node::Num(0.0),
+        (a, Node::Num(1.0)) => a,
+        (a, b) => Node::Bin(Bin::Div, Box::new(a), Box::new(b)),
+    }
+}
+
+fn pow(a: Node, b: Node) -> Node {
+    match (a, b) {
+        (Node::Num(x), Node::Num(y)) => Node::Num(x.powf(y)),
+        (_, Node::Num(0.0)) => Node::Num(1.0),
+        (a, Node::Num(1.0)) => a,
+        (a, b) => Node::Bin(Bin::Pow, Box::new(a), Box::new(b)),
+    }
+}
+
+fn fn1(f: Fn1, a: Node) -> Node {
+    match a {
+        Node::Num(x) => Node::Num(f.ptr()(x)),
+        a => Node::Fn1(f, Box::new(a)),
+    }
+}
+
+fn fn2(f: Fn2, a: Node, b: Node) -> Node {
+    Node::Fn2(f, Box::new(a), Box::new(b))
+}
+
+impl Fn1 {
+    fn ptr(self) -> fn(f64) -> f64 {
+        use Fn1::*;
+        match self {
+            Log => f64::log10,
+            Sqrt => f64::sqrt,
+            Exp => f64::exp,
+            Ln => f64::ln,
+            Abs => f64::abs,
+            Sin => f64::sin,
+            Cos => f64::cos,
+            Tan => f64::tan,
+            Asin => f64::asin,
+            Acos => f64::acos,
+            Atan => f64::atan,
+            Sinh => f64::sinh,
+            Cosh => f64::cosh,
+            Tanh => f64::tanh,
+            Asinh => f64::asinh,
+            Acosh => f64::acosh,
+            Atanh => f64::atanh,
+            Floor => f64::floor,
+            Ceil => f64::ceil,
+            Round => f64::round,
+            Signum => f64::signum,
+            Trunc => f64::trunc,
+        }
+    }
+}
+
+impl Fn2 {
+    fn ptr(self) -> fn(f64, f64) -> f64 {
+        match self {
+            Fn2::Atan2 => f64::atan2,
+            Fn2::Eq => |a, b| if a == b { 1.0 } else { 0.0 },
+        }
+    }
+}
+
+impl FnN {
+    fn ptr(self) -> fn(&[f64]) -> f64 {
+        match self {
+            FnN::Max => meval::max_array,
+            FnN::Min => meval::min_array,
+        }
+    }
+}
+
+/// Resolve a function name into a node, popping the arguments off the stack.
+///
+/// This mirrors the functions available in [`ctx`].
+fn func(name: &str, args: usize, stack: &mut Vec<Node>) -> Result<Node> {
+    use Fn1::*;
+
+    let f1 = match name {
+        "log" => Some(Log),
+        "sqrt" => Some(Sqrt),
+        "exp" => Some(Exp),
+        "ln" => Some(Ln),
+        "abs" => Some(Abs),
+        "sin" => Some(Sin),
+        "cos" => Some(Cos),
+        "tan" => Some(Tan),
+        "asin" => Some(Asin),
+        "acos" => Some(Acos),
+        "atan" => Some(Atan),
+        "sinh" => Some(Sinh),
+        "cosh" => Some(Cosh),
+        "tanh" => Some(Tanh),
+        "asinh" => Some(Asinh),
+        "acosh" => Some(Acosh),
+        "atanh" => Some(Atanh),
+        "floor" => Some(Floor),
+        "ceil" => Some(Ceil),
+        "round" => Some(Round),
+        "signum" => Some(Signum),
+        _ => None,
+    };
+
+    if let Some(f) = f1 {
+        ensure!(args == 1, "function '{}' expects 1 argument", name);
+        return Ok(Node::Fn1(f, pop_node(stack)?));
+    }
+
+    match name {
+        "atan2" => {
+            ensure!(args == 2, "function 'atan2' expects 2 arguments");
+            let b = pop_node(stack)?;
+            let a = pop_node(stack)?;
+            Ok(Node::Fn2(Fn2::Atan2, a, b))
+        }
+        "max" | "min" if args > 0 => {
+            ensure!(stack.len() >= args, "expression stack underflow");
+            let xs = stack.split_off(stack.len() - args);
+            let f = if name == "max" { FnN::Max } else { FnN::Min };
+            Ok(Node::FnN(f, xs))
+        }
+        _ => bail!("unknown function '{}' with {} arguments", name, args),
+    }
+}
+
+/// A compiled expression, evaluated as a stack machine.
+#[derive(Clone)]
+struct Program {
+    ops: Vec<Op>,
+    /// The maximum stack depth required to evaluate the program.
+    depth: usize,
+}
+
+#[derive(Copy, Clone)]
+enum Op {
+    Num(f64),
+    /// Index into the parameters.
+    Param(usize),
+    /// Index into the variables.
+    Var(usize),
+    Neg,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    Fn1(fn(f64) -> f64),
+    Fn2(fn(f64, f64) -> f64),
+    /// A function which takes a variable number of arguments.
+    FnN(fn(&[f64]) -> f64, usize),
+}
+
+impl Program {
+    /// Compile the tree into a program.
+    fn compile(tree: &Node) -> Self {
+        let mut x = Self {
+            ops: Vec::new(),
+            depth: 0,
+        };
+        x.emit(tree, 0);
+        x
+    }
+
+    /// Emit the operations for `node` in post-order, `len` is the stack length before `node` is
+    /// evaluated.
+    fn emit(&mut self, node: &Node, len: usize) {
+        let op = match node {
+            Node::Num(x) => Op::Num(*x),
+            Node::Param(i) => Op::Param(*i),
+            Node::Var(i) => Op::Var(*i),
+            Node::Neg(a) => {
+                self.emit(a, len);
+                Op::Neg
+            }
+            Node::Fn1(f, a) => {
+                self.emit(a, len);
+                Op::Fn1(f.ptr())
+            }
+            Node::Bin(op, a, b) => {
+                self.emit(a, len);
+                self.emit(b, len + 1);
+                match op {
+                    Bin::Add => Op::Add,
+                    Bin::Sub => Op::Sub,
+                    Bin::Mul => Op::Mul,
+                    Bin::Div => Op::Div,
+                    Bin::Rem => Op::Rem,
+                    Bin::Pow => Op::Pow,
+                }
+            }
+            Node::Fn2(f, a, b) => {
+                self.emit(a, len);
+                self.emit(b, len + 1);
+                Op::Fn2(f.ptr())
+            }
+            Node::FnN(f, xs) => {
+                for (i, x) in xs.iter().enumerate() {
+                    self.emit(x, len + i);
+                }
+                Op::FnN(f.ptr(), xs.len())
+            }
+        };
+
+        self.depth = self.depth.max(len + 1);
+        self.ops.push(op);
+    }
+
+    /// Evaluate the program.
+    ///
+    /// `stack` is used as scratch space, it is cleared before use.
+    fn eval(&self, params: &[f64], vars: &[f64], stack: &mut Vec<f64>) -> f64 {
+        stack.clear();
+
+        for op in &self.ops {
+            let x = match *op {
+                Op::Num(x) => x,
+                Op::Param(i) => params[i],
+                Op::Var(i) => vars[i],
+                Op::Neg => -pop(stack),
+                Op::Fn1(f) => f(pop(stack)),
+                Op::FnN(f, n) => {
+                    let at = stack.len() - n;
+                    let x = f(&stack[at..]);
+                    stack.truncate(at);
+                    x
+                }
+                Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Rem | Op::Pow | Op::Fn2(_) => {
+                    let r = pop(stack);
+                    let l = pop(stack);
+                    match *op {
+                        Op::Add => l + r,
+                        Op::Sub => l - r,
+                        Op::Mul => l * r,
+                        Op::Div => l / r,
+                        Op::Rem => l % r,
+                        Op::Pow => l.powf(r),
+                        Op::Fn2(f) => f(l, r),
+                        _ => unreachable!(),
+                    }
+                }
+            };
+            stack.push(x);
+        }
+
+        pop(stack)
+    }
+}
+
+fn pop(stack: &mut Vec<f64>) -> f64 {
+    stack.pop().expect("compiled program has a valid stack")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiled_matches_meval() {
+        let exprs = [
+            "m * x + c",
+            "-a * x ^ 2 + b % 3 - -x",
+            "sin(x) + ln(x) + cos(x) + tan(x) + log(x) + sqrt(x) + exp(x) + abs(x) + k",
+            "0.5 * (1 + tanh(((x - Mean) * sqrt(pi) * log(2)) / (Stdev^2 * sqrt(2))))",
+            "max(a, x, 2) - min(x, b) + atan2(a, x) * e + signum(-b)",
+        ];
+        let hdrs = Headers::from_iter(["x"]);
+
+        for e in exprs {
+            let eq = Eq::parse(e, &hdrs).unwrap();
+            let params = (0..eq.params_len())
+                .map(|i| i as f64 * 0.7 + 0.3)
+                .collect::<Vec<_>>();
+
+            for x in [0.1, 1.5, 4.0] {
+                let mut names = eq.params();
+                names.push("x".into());
+                let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+                let f = e
+                    .parse::<Expr>()
+                    .unwrap()
+                    .bindn_with_context(ctx(), &names)
+                    .unwrap();
+                let mut inputs = params.clone();
+                inputs.push(x);
+                let exp = f(&inputs);
+
+                let data = Data::new(Headers::from_iter(["x"]), [[x]]).unwrap();
+                let act = eq.solve(&params, data.rows().next().unwrap()).unwrap();
+
+                assert_eq!(exp.to_bits(), act.to_bits(), "{e} with x = {x}");
+            }
+        }
+    }
+
+    #[test]
+    fn categorical_levels() {
+        let mut data = Data::new(
+            Headers::from_iter(["g", "x"]),
+            [("b", 1.0), ("a", 2.0), ("c", 3.0), ("b", 4.0)]
+                .map(|(g, x)| [Cell::from(g), Cell::from(x)]),
+        )
+        .unwrap();
+        // text columns are variables until made categorical
+        assert_eq!(Eq::parse("a * g", data.headers()).unwrap().vars(), ["g"]);
+        data.set_categorical(0).unwrap();
+
+        let eq = Eq::parse("(a + m * x) * G + c", data.headers()).unwrap();
+        assert_eq!(eq.params(), ["a[b]", "a[c]", "c", "m[b]", "m[c]"]);
+        assert_eq!(eq.vars(), ["x"]);
+        assert_eq!(eq.categorical(), ["G"]);
+
+        let params = [1.0, 2.0, 10.0, 0.5, -0.5];
+        let mut out = vec![0.0; data.len()];
+        eq.solve_batch(&params, &data, &mut out).unwrap();
+        assert_eq!(out, [11.5, 10.0, 10.5, 13.0]);
+        let r = data.rows().nth(2).unwrap();
+        assert_eq!(eq.solve(&params, r), Some(10.5));
+
+        let mut jac = vec![0.0; data.len() * 5];
+        eq.jacobian_batch(&params, &data, &mut jac).unwrap();
+        assert_eq!(&jac[..5], &[1.0, 0.0, 1.0, 1.0, 0.0]);
+        assert_eq!(&jac[5..10], &[0.0, 0.0, 1.0, 0.0, 0.0]);
+
+        for e in ["sin(g) * a", "x * g", "a * g * g"] {
+            assert!(Eq::parse(e, data.headers()).is_err(), "{e}");
+        }
+    }
+
+    #[test]
+    fn derivatives_match_finite_differences() {
+        let exprs = [
+            "m * x + c",
+            "a * exp(-b * x) + c",
+            "-a * x ^ 2 + b % 3 - -x / b",
+            "x ^ a + a ^ x + sqrt(a * x) + ln(b * x) + log(b + x)",
+            "sin(a * x) + cos(b) * tan(a) + asin(a / 3) + acos(b / 4) + atan(a * b)",
+            "sinh(a) + cosh(b * x) + tanh(a * x) + asinh(b) + acosh(a + 2) + atanh(b / 5)",
+            "abs(a - x) + floor(b) * a + max(a, x, b) - min(x, b) + atan2(a, b * x)",
+            "0.5 * (1 + tanh(((x - Mean) * sqrt(pi) * log(2)) / (Stdev^2 * sqrt(2))))",
+        ];
+        let data = Data::new(Headers::from_iter(["x"]), [[0.3], [1.7], [2.2], [4.1]]).unwrap();
+
+        for e in exprs {
+            let eq = Eq::parse(e, data.headers()).unwrap();
+            let n = eq.params_len();
+            let params = (0..n).map(|i| i as f64 * 0.4 + 0.7).collect::<Vec<_>>();
+
+            let mut jac = vec![0.0; data.len() * n];
+            eq.jacobian_batch(&params, &data, &mut jac).unwrap();
+
+            for p in 0..n {
+                let h = 1e-6;
+                let mut hi = params.clone();
+                hi[p] += h;
+                let mut lo = params.clone();
+                lo[p] -= h;
+
+                let mut yhi = vec![0.0; data.len()];
+                let mut ylo = vec![0.0; data.len()];
+                eq.solve_batch(&hi, &data, &mut yhi).unwrap();
+                eq.solve_batch(&lo, &data, &mut ylo).unwrap();
+
+                for r in 0..data.len() {
+                    let exp = (yhi[r] - ylo[r]) / (2.0 * h);
+                    let act = jac[r * n + p];
+                    assert!(
+                        (exp - act).abs() <= 1e-5 * exp.abs().max(1.0),
+                        "{e} d/d{} row {r}: expecting {exp} found {act}",
+                        eq.params()[p]
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! Filtering data rows with conditional expressions.
+use super::*;
+use data::{DataRow, Headers};
+
+/// A row filter, such as `temp > 20 && site == 'north'`.
+///
+/// Each side of a comparison is an equation of columns, parsed with the same resolver as the
+/// fitted equation. Comparisons are `<`, `<=`, `>`, `>=`, `==` and `!=`, combined with `&&` and
+/// `||` (where `&&` binds tighter).
+/// A text column can be compared to a (optionally quoted) literal with `==` and `!=`, operators
+/// inside quotes are part of the literal.
+pub struct Filter<E> {
+    /// Rows are kept if all the comparisons of any group are true.
+    any: Vec<Vec<Cmp<E>>>,
+}
+
+enum Cmp<E> {
+    Num(E, Op, E),
+    Txt { col: usize, eq: bool, value: String },
+}
+
+#[derive(Copy, Clone)]
+enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+/// The comparison operators, two character operators first so they match before `<` and `>`.
+const OPS: [(&str, Op); 6] = [
+    ("<=", Op::Le),
+    (">=", Op::Ge),
+    ("==", Op::Eq),
+    ("!=", Op::Ne),
+    ("<", Op::Lt),
+    (">", Op::Gt),
+];
+
+impl<E: Equation> Filter<E> {
+    /// Parse a filter expression against the `columns`.
+    pub fn parse(expr: &str, columns: &Headers) -> Result<Self> {
+        let any = split_unquoted(expr, "||")
+            .into_iter()
+            .map(|all| {
+                split_unquoted(all, "&&")
+                    .into_iter()
+                    .map(|x| Cmp::parse(x.trim(), columns))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()
+            .wrap_err_with(|| format!("in filter: {expr}"))?;
+
+        Ok(Self { any })
+    }
+
+    /// Test if the row passes the filter.
+    ///
+    /// Fails if a comparison cannot be evaluated, such as a text cell in a numeric column.
+    pub fn keep(&self, row: DataRow) -> Result<bool> {
+        for all in &self.any {
+            let mut keep = true;
+            for cmp in all {
+                if !cmp.eval(row)? {
+                    keep = false;
+                    break;
+                }
+            }
+            if keep {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+/// The sides of each comparison in the filter `expr`, such as to find the columns it uses.
+///
+/// Comparisons without an operator are skipped.
+pub(crate) fn sides(expr: &str) -> impl Iterator<Item = &str> {
+    split_unquoted(expr, "||")
+        .into_iter()
+        .flat_map(|all| split_unquoted(all, "&&"))
+        .filter_map(split_cmp)
+        .flat_map(|(lhs, _, rhs)| [lhs, rhs])
+}
+
+/// Split a comparison at its first operator outside of quotes into the trimmed sides.
+fn split_cmp(s: &str) -> Option<(&str, Op, &str)> {
+    let (at, sym, op) = unquoted(s).find_map(|i| {
+        OPS.iter()
+            .find(|(sym, _)| s[i..].starts_with(sym))
+            .map(|&(sym, op)| (i, sym, op))
+    })?;
+    Some((s[..at].trim(), op, s[at + sym.len()..].trim()))
+}
+
+/// Split `s` at each `sep` outside of quotes.
+fn split_unquoted<'a>(s: &'a str, sep: &str) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for i in unquoted(s) {
+        if i >= start && s[i..].starts_with(sep) {
+            parts.push(&s[start..i]);
+            start = i + sep.len();
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// The byte index of each char outside of a `'` or `"` quoted literal, excluding the quotes.
+fn unquoted(s: &str) -> impl Iterator<Item = usize> + '_ {
+    let mut quote = None;
+    s.char_indices().filter_map(move |(i, c)| match quote {
+        Some(q) => {
+            if c == q {
+                quote = None;
+            }
+            None
+        }
+        None if c == '\'' || c == '"' => {
+            quote = Some(c);
+            None
+        }
+        None => Some(i),
+    })
+}
+
+impl<E: Equation> Cmp<E> {
+    fn parse(s: &str, columns: &Headers) -> Result<Self> {
+        let (lhs, op, rhs) = split_cmp(s)
+            .ok_or_else(|| miette!("expecting a comparison such as `x > 1`, found '{}'", s))?;
+
+        // a text column compares to a literal
+        if let Op::Eq | Op::Ne = op {
+            let txt = |x: &str| {
+                columns
+                    .find_ignore_case_and_ws(x)
+                    .filter(|&i| columns.is_text(i))
+            };
+            if let Some((col, value)) = txt(lhs).map(|i| (i, rhs)).or(txt(rhs).map(|i| (i, lhs))) {
+                let value = value
+                    .strip_prefix(['\'', '"'])
+                    .and_then(|x| x.strip_suffix(['\'', '"']))
+                    .unwrap_or(value);
+                return Ok(Cmp::Txt {
+                    col,
+                    eq: matches!(op, Op::Eq),
+                    value: value.to_string(),
+                });
+            }
+        }
+
+        let side = |x: &str| {
+            let eq = E::parse(x, columns)?;
+            let unknown = eq.params();
+            ensure!(
+                unknown.is_empty(),
+                "unknown columns in filter: {}",
+                unknown.join(", ")
+            );
+            Ok(eq)
+        };
+
+        Ok(Cmp::Num(side(lhs)?, op, side(rhs)?))
+    }
+
+    fn eval(&self, row: DataRow) -> Result<bool> {
+        match self {
+            Cmp::Num(lhs, op, rhs) => {
+                let eval = |e: &E| {
+                    e.solve(&[], row).ok_or_else(|| {
+                        miette!(
+                            "failed to evaluate '{}' in row index {}",
+                            e.expr().unwrap_or_default(),
+                            row.idx() + 1
+                        )
+                    })
+                };
+                let (a, b) = (eval(lhs)?, eval(rhs)?);
+                Ok(match op {
+                    Op::Lt => a < b,
+                    Op::Le => a <= b,
+                    Op::Gt => a > b,
+                    Op::Ge => a >= b,
+                    Op::Eq => a == b,
+                    Op::Ne => a != b,
+                })
+            }
+            Cmp::Txt { col, eq, value } => Ok((row.get_txt(*col) == Some(value)) == *eq),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::{Cell, Data};
+
+    #[test]
+    fn comparisons() {
+        let data = Data::new(
+            Headers::from_iter(["x", "y", "site"]),
+            [
+                (1.0, 4.0, "north"),
+                (2.0, 3.0, "south"),
+                (3.0, 2.0, "north"),
+            ]
+            .map(|(x, y, s)| [Cell::from(x), Cell::from(y), Cell::from(s)]),
+        )
+        .unwrap();
+
+        let kept = |s: &str| {
+            let f = Filter::<expr::v1::Eq>::parse(s, data.headers()).unwrap();
+            data.rows()
+                .filter_map(|r| f.keep(r).unwrap().then_some(r.idx()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(kept("x > 1"), [1, 2]);
+        assert_eq!(kept("x >= 2 && x < 3"), [1]);
+        assert_eq!(kept("x == 1 || y == 2"), [0, 2]);
+        assert_eq!(kept("x * 2 != y + 1"), [0, 2]);
+        assert_eq!(kept("x<=y"), [0, 1]);
+        assert_eq!(kept("site == 'north'"), [0, 2]);
+        assert_eq!(kept("south != Site && x > 1"), [2]);
+
+        let parse = |s: &str| Filter::<expr::v1::Eq>::parse(s, data.headers());
+        assert!(parse("x").is_err());
+        assert!(parse("x > k").is_err());
+        assert!(parse("x > ").is_err());
+
+        assert_eq!(
+            sides("x * 2 != y + 1 || site == 'north' && x").collect::<Vec<_>>(),
+            ["x * 2", "y + 1", "site", "'north'"]
+        );
+    }
+
+    #[test]
+    fn quoted_operators() {
+        let data = Data::new(
+            Headers::from_iter(["x", "name"]),
+            [(1.0, "a&&b"), (2.0, "c||d"), (3.0, "x<y")]
+                .map(|(x, s)| [Cell::from(x), Cell::from(s)]),
+        )
+        .unwrap();
+
+        let kept = |s: &str| {
+            let f = Filter::<expr::v1::Eq>::parse(s, data.headers()).unwrap();
+            data.rows()
+                .filter_map(|r| f.keep(r).unwrap().then_some(r.idx()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(kept(r#"name == "a&&b""#), [0]);
+        assert_eq!(kept("name == 'c||d' || x > 2"), [1, 2]);
+        assert_eq!(kept("'x<y' == name"), [2]);
+        assert_eq!(kept("name != 'a&&b' && name != \"x<y\""), [1]);
+
+        assert_eq!(
+            sides("name == 'a&&b' || x > 1").collect::<Vec<_>>(),
+            ["name", "'a&&b'", "x", "1"]
+        );
+    }
+
+    #[test]
+    fn non_ascii() {
+        let data = Data::new(
+            Headers::from_iter(["tempé", "lieu"]),
+            [(1.0, "nörd"), (2.0, "süd")].map(|(x, s)| [Cell::from(x), Cell::from(s)]),
+        )
+        .unwrap();
+
+        let kept = |s: &str| {
+            let f = Filter::<expr::v1::Eq>::parse(s, data.headers()).unwrap();
+            data.rows()
+                .filter_map(|r| f.keep(r).unwrap().then_some(r.idx()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(kept("lieu == 'süd'"), [1]);
+        assert_eq!(kept("nörd != lieu"), [1]);
+        // equations do not support non-ASCII variables, which is an error rather than a panic
+        let parse = |s: &str| Filter::<expr::v1::Eq>::parse(s, data.headers());
+        assert!(parse("tempé > 1").is_err());
+        assert!(parse("lieu == süd && tempé <= 2").is_err());
+    }
+}
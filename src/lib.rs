@@ -8,28 +8,47 @@
 //! _If using as a library, see the [`fit`] function's documentation for an example._
 #![warn(missing_docs)]
 
-use clap::{Parser, ValueEnum};
+use clap::{Args, Parser, ValueEnum};
 use miette::*;
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     fs,
     io::{self, Write},
     path::PathBuf,
 };
 
 mod data;
+mod derived;
 pub mod expr;
+mod filter;
+mod model;
 mod solve;
+mod stats;
 
-pub use data::{Data, DataRow, Headers};
+pub use data::{Cell, Data, DataRow, Headers};
+pub use derived::Derived;
 pub use expr::Equation;
-pub use solve::{fit, Fit};
+pub use filter::Filter;
+pub use model::Model;
+pub use solve::{
+    compare, cross_validate, fit, fit_groups, fit_with, Candidate, Comparison, CrossValidation,
+    Fit, FitOptions, Fold, Forecast, Group, Grouped, NestedTest, ParamBound, ParamValue,
+    Prediction, SolverConfig, Weights,
+};
 
 /// CLI curve fitting tool.
 /// Parameterise an equation from a CSV dataset.
 #[derive(Parser, Debug)]
-#[command(author, version, about)]
+#[command(
+    author,
+    version,
+    about,
+    after_help = "Use `fitme predict --help` to evaluate a fitted model on new data."
+)]
 pub struct App {
     /// The target column (the Y value).
+    /// A target named `predict` must follow `--`, as the first argument `predict` is the command.
     pub target: String,
 
     /// The parameterised equation.
@@ -39,10 +58,169 @@ pub struct App {
     /// If left blank, stdin is read.
     pub data: Option<PathBuf>,
 
+    /// How the input CSV is formatted.
+    #[command(flatten)]
+    pub dialect: Dialect,
+
+    /// Add a column computed from other columns, eg `--let "logx = ln(x)"`.
+    /// Can be given multiple times, each definition can use the columns defined before it.
+    /// Derived columns can be used in the equation and filters, and are written with the
+    /// residuals.
+    #[arg(long = "let", value_name = "NAME=EXPR")]
+    pub derived: Vec<String>,
+
+    /// How rows with missing values are handled.
+    /// Only the columns used by the equations, derived columns, filters, target, weights and groups
+    /// are checked.
+    #[arg(long, default_value_t, value_enum)]
+    pub missing: Missing,
+
+    /// A cell value which is missing, replacing the defaults `""`, `NA`, `NaN` and `null`.
+    /// Can be given multiple times. Cells which parse as `NaN` are always missing.
+    #[arg(long = "na", value_name = "TOKEN")]
+    pub na: Vec<String>,
+
+    /// Treat a text column as categorical, eg `--categorical class`.
+    /// Can be given multiple times. Terms multiplied by a categorical column are fitted with a
+    /// parameter for each level, see the README.
+    #[arg(long, value_name = "COLUMN")]
+    pub categorical: Vec<String>,
+
+    /// Only fit rows matching a condition, eg `--where "temp > 20"`.
+    /// Comparisons (`<`, `<=`, `>`, `>=`, `==`, `!=`) can be combined with `&&` and `||`.
+    /// Can be given multiple times, rows must match every condition.
+    #[arg(long = "where", value_name = "EXPR")]
+    pub filter: Vec<String>,
+
     /// The version of equation resolver to use.
     #[arg(long, default_value_t, value_enum)]
     pub eq_resolver: EquationResolver,
 
+    /// How the partial derivatives of the equation are calculated.
+    #[arg(long, default_value_t, value_enum)]
+    pub derivatives: Derivatives,
+
+    /// Constrain a parameter within bounds, eg `--bound k=0..` or `--bound k=-1..1`.
+    /// Either side of the range can be left open.
+    #[arg(long, value_name = "PARAM=LOW..HIGH")]
+    pub bound: Vec<ParamBound>,
+
+    /// Hold a parameter at a fixed value, eg `--fix c=3.2`.
+    #[arg(long, value_name = "PARAM=VALUE")]
+    pub fix: Vec<ParamValue>,
+
+    /// Initial values for parameters, eg `--init a=1.5,b=-0.2`.
+    /// Parameters not given are guessed.
+    #[arg(long, value_name = "PARAM=VALUE", value_delimiter = ',')]
+    pub init: Vec<ParamValue>,
+
+    /// Refine from this many starting points, keeping the best fit.
+    /// The starting points are sampled within any parameter bounds.
+    #[arg(long, value_name = "STARTS")]
+    pub multistart: Option<usize>,
+
+    /// How the multi-start starting points are sampled.
+    #[arg(long, default_value_t, value_enum)]
+    pub sampling: Sampling,
+
+    /// Seed the random number generator, making results reproducible.
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Weight each observation's residual by this column.
+    #[arg(long, value_name = "COLUMN", conflicts_with = "sigma")]
+    pub weights: Option<String>,
+
+    /// Weight each observation's residual by the inverse variance, where this column holds each
+    /// observation's standard deviation.
+    #[arg(long, value_name = "COLUMN")]
+    pub sigma: Option<String>,
+
+    /// The loss function to minimise.
+    /// Robust losses reduce the influence of outliers.
+    #[arg(long, default_value_t, value_enum)]
+    pub loss: Loss,
+
+    /// The tuning constant of a robust loss, in units of the residuals' robust standard deviation.
+    /// Defaults to the constant giving 95% efficiency for normal residuals: 1.345 (huber), 1.287
+    /// (soft-l1), 2.385 (cauchy) or 4.685 (tukey).
+    #[arg(long, value_name = "C")]
+    pub loss_tuning: Option<f64>,
+
+    /// The confidence level of the parameter confidence intervals.
+    #[arg(long, default_value_t = 0.95, value_name = "LEVEL")]
+    pub confidence: f64,
+
+    /// Estimate the parameter uncertainties by refitting this many bootstrap resamples.
+    /// Reports bootstrap standard errors and percentile confidence intervals.
+    #[arg(long, value_name = "N")]
+    pub bootstrap: Option<usize>,
+
+    /// What the bootstrap resamples.
+    #[arg(long, default_value_t, value_enum)]
+    pub resample: Resample,
+
+    /// Maximum number of solver iterations.
+    #[arg(long, default_value_t = SolverConfig::default().max_iter)]
+    pub max_iter: usize,
+
+    /// Relative tolerance of the residual sum of squares for convergence.
+    #[arg(long, default_value_t = SolverConfig::default().ftol)]
+    pub ftol: f64,
+
+    /// Relative tolerance of the parameter values for convergence.
+    #[arg(long, default_value_t = SolverConfig::default().xtol)]
+    pub xtol: f64,
+
+    /// Orthogonality tolerance for convergence.
+    #[arg(long, default_value_t = SolverConfig::default().gtol)]
+    pub gtol: f64,
+
+    /// Relative step size of numeric derivatives.
+    #[arg(long, default_value_t = SolverConfig::default().epsfcn)]
+    pub epsfcn: f64,
+
+    /// Initial step bound of the numeric derivatives solver.
+    #[arg(long, default_value_t = SolverConfig::default().step_factor)]
+    pub step_factor: f64,
+
+    /// Compare the fit of another equation, ranking the equations by AIC.
+    /// Can be given multiple times.
+    #[arg(
+        long,
+        value_name = "EXPR",
+        conflicts_with_all = ["bootstrap", "residuals", "save_model", "covariance", "no_stats"]
+    )]
+    pub compare: Vec<String>,
+
+    /// Compare the fits of the equations in a file, one per line.
+    /// Blank lines and lines starting with `#` are ignored.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["bootstrap", "residuals", "save_model", "covariance", "no_stats"]
+    )]
+    pub compare_file: Option<PathBuf>,
+
+    /// Cross-validate the fit with this many folds, reporting the out-of-sample error of each
+    /// fold instead of the fit. Each fold holds out at least two rows.
+    /// The rows are shuffled into folds, use `--seed` for reproducible folds.
+    #[arg(
+        long,
+        value_name = "K",
+        conflicts_with_all = ["compare", "compare_file", "bootstrap", "residuals", "save_model", "covariance"]
+    )]
+    pub cv: Option<usize>,
+
+    /// Fit each group of rows sharing a value of this column independently, outputting one table
+    /// keyed by group.
+    #[arg(
+        long,
+        value_name = "COLUMN",
+        conflicts_with_all = ["compare", "compare_file", "cv", "bootstrap", "residuals", "save_model", "covariance"]
+    )]
+    pub group_by: Option<String>,
+
     /// The output format to write to stdout.
     #[arg(short, long, default_value_t, value_enum)]
     pub out: Output,
@@ -51,20 +229,224 @@ pub struct App {
     #[arg(short, long)]
     pub no_stats: bool,
 
+    /// Write each observation with its prediction, residual, standardised residual and leverage
+    /// to a file.
+    /// The file is JSON if the path has a `.json` extension, otherwise CSV.
+    #[arg(long, value_name = "PATH")]
+    pub residuals: Option<PathBuf>,
+
+    /// Save the fitted model to a file, which can be used with `fitme predict`.
+    /// The model stores the equation, target, options and fit as JSON.
+    #[arg(long, value_name = "PATH")]
+    pub save_model: Option<PathBuf>,
+
+    /// Output the parameter covariance and correlation matrices.
+    /// The JSON output always includes the matrices.
+    #[arg(long)]
+    pub covariance: bool,
+
     /// Output debug information about the expression and input data.
     /// Does not attempt a fit.
     #[arg(long)]
     pub debug: bool,
 }
 
+/// Commands which use a fitted model.
+///
+/// These are parsed separately to [`App`], when the first argument is a command name.
+#[derive(Parser, Debug)]
+#[command(name = "fitme", author, version)]
+pub enum Command {
+    /// Evaluate a fitted model on new data.
+    Predict(Predict),
+}
+
+impl Command {
+    /// Is `arg` the name of a command?
+    pub fn is_command(arg: &std::ffi::OsStr) -> bool {
+        use clap::CommandFactory;
+        Self::command()
+            .get_subcommands()
+            .any(|c| arg == c.get_name())
+    }
+
+    /// Run the command.
+    pub fn run(self) -> Result<()> {
+        match self {
+            Command::Predict(x) => x.run(),
+        }
+    }
+}
+
+/// Evaluate a fitted model on new data, writing each row with its predicted value.
+#[derive(Args, Debug)]
+pub struct Predict {
+    /// Path to the fitted model, either a saved model (`--save-model`) or the JSON output of a fit
+    /// (`-o=json`).
+    pub fit: PathBuf,
+
+    /// Path to input CSV file.
+    /// If left blank, stdin is read.
+    pub data: Option<PathBuf>,
+
+    /// The parameterised equation which was fitted.
+    /// Required for the JSON output of a fit, a saved model stores its equation.
+    #[arg(long)]
+    pub expr: Option<String>,
+
+    /// How the input CSV is formatted.
+    #[command(flatten)]
+    pub dialect: Dialect,
+
+    /// Add a column computed from other columns, eg `--let "logx = ln(x)"`.
+    /// Use the same definitions as the fit, a saved model stores its definitions.
+    #[arg(long = "let", value_name = "NAME=EXPR")]
+    pub derived: Vec<String>,
+
+    /// The version of equation resolver to use.
+    /// Defaults to the saved model's resolver, or version 1.
+    #[arg(long, value_enum)]
+    pub eq_resolver: Option<EquationResolver>,
+
+    /// Include the standard error of each prediction and its confidence and prediction bands.
+    #[arg(long)]
+    pub bands: bool,
+
+    /// The confidence level of the bands.
+    #[arg(long, default_value_t = 0.95, value_name = "LEVEL")]
+    pub confidence: f64,
+
+    /// The output format to write to stdout.
+    #[arg(short, long, default_value_t, value_enum)]
+    pub out: RowOutput,
+}
+
+/// How the input CSV is formatted.
+#[derive(Args, Debug, Clone)]
+pub struct Dialect {
+    /// The field delimiter, a single ASCII character or `\t` for tabs.
+    #[arg(long, default_value = ",", value_name = "CHAR", value_parser = parse_ascii)]
+    pub delimiter: u8,
+
+    /// The quote character, a single ASCII character.
+    #[arg(long, default_value = "\"", value_name = "CHAR", value_parser = parse_ascii)]
+    pub quote: u8,
+
+    /// Ignore lines starting with this character, eg `--comment #`.
+    #[arg(long, value_name = "CHAR", value_parser = parse_ascii)]
+    pub comment: Option<u8>,
+
+    /// The first row is data, not headers.
+    /// Columns are named by their index: `c0`, `c1`, ...
+    #[arg(long)]
+    pub no_header: bool,
+
+    /// Skip this many lines before the header (or first data row).
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    pub skip_rows: usize,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            comment: None,
+            no_header: false,
+            skip_rows: 0,
+        }
+    }
+}
+
+fn parse_ascii(s: &str) -> std::result::Result<u8, String> {
+    match s.as_bytes() {
+        [c] if c.is_ascii() => Ok(*c),
+        b"\\t" => Ok(b'\t'),
+        _ => Err(format!("expecting a single ASCII character, found '{s}'")),
+    }
+}
+
 /// Versions of the equation resolver.
-#[derive(Debug, Copy, Clone, ValueEnum, Default)]
+#[derive(Debug, Copy, Clone, PartialEq, ValueEnum, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum EquationResolver {
     /// Version #1.
     #[default]
     V1,
 }
 
+/// How the partial derivatives of the equation, with respect to each parameter, are calculated.
+#[derive(Debug, Copy, Clone, ValueEnum, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Derivatives {
+    /// Symbolically differentiate the equation, falling back to numeric differencing if the
+    /// equation resolver does not support it.
+    #[default]
+    Analytic,
+
+    /// Approximate the derivatives with finite differences.
+    Numeric,
+}
+
+/// How the starting points of a multi-start search are sampled.
+#[derive(Debug, Copy, Clone, ValueEnum, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Sampling {
+    /// Latin hypercube sampling, spreading the starts evenly across each parameter's range.
+    #[default]
+    Lhs,
+
+    /// Uniform random sampling.
+    Random,
+}
+
+/// What a bootstrap resamples.
+#[derive(Debug, Copy, Clone, ValueEnum, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Resample {
+    /// Resample the data rows with replacement.
+    #[default]
+    Rows,
+
+    /// Keep the data rows, resampling the fitted residuals with replacement.
+    /// Suited to a fixed design, where the variables are controlled.
+    /// Observations with a weight of zero are left out of the resampled residuals.
+    Residuals,
+}
+
+/// The loss function minimised by the fit.
+#[derive(Debug, Copy, Clone, ValueEnum, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Loss {
+    /// Least squares, the sum of squared residuals.
+    #[default]
+    Squared,
+
+    /// Squared for small residuals, linear for large residuals.
+    Huber,
+
+    /// A smooth approximation of the Huber loss.
+    #[value(name = "soft-l1")]
+    SoftL1,
+
+    /// Logarithmic growth, strongly discounting large residuals.
+    Cauchy,
+
+    /// Tukey's biweight, which ignores residuals beyond the tuning constant.
+    Tukey,
+}
+
+/// How are rows with missing values handled?
+#[derive(Debug, Copy, Clone, ValueEnum, Default)]
+pub enum Missing {
+    /// Fail on the first missing value.
+    #[default]
+    Error,
+
+    /// Drop rows with missing values, reporting the number dropped.
+    Drop,
+}
+
 /// How do you want the output formatted?
 #[derive(Debug, Copy, Clone, ValueEnum, Default)]
 pub enum Output {
@@ -85,6 +467,17 @@ pub enum Output {
     Json,
 }
 
+/// How are data rows formatted?
+#[derive(Debug, Copy, Clone, ValueEnum, Default)]
+pub enum RowOutput {
+    /// Comma separated value output.
+    #[default]
+    Csv,
+
+    /// An array of objects, keyed by column.
+    Json,
+}
+
 impl App {
     /// Fit data and output results.
     pub fn run(self) -> Result<()> {
@@ -94,6 +487,32 @@ impl App {
     }
 }
 
+impl Predict {
+    /// Predict data with a fitted model and output results.
+    pub fn run(self) -> Result<()> {
+        let fitted = Fitted::read(&self.fit)
+            .wrap_err_with(|| format!("failed to read fit from '{}'", self.fit.display()))?;
+
+        let eq_resolver = match (&fitted, self.eq_resolver) {
+            (Fitted::Model(m), Some(r)) => {
+                ensure!(
+                    m.eq_resolver == r,
+                    "equation resolver {:?} does not match the model's resolver {:?}",
+                    r,
+                    m.eq_resolver
+                );
+                r
+            }
+            (Fitted::Model(m), None) => m.eq_resolver,
+            (Fitted::Fit(_), r) => r.unwrap_or_default(),
+        };
+
+        match eq_resolver {
+            EquationResolver::V1 => predict::<expr::v1::Eq>(self, fitted),
+        }
+    }
+}
+
 fn run<E>(app: App) -> Result<()>
 where
     E: Equation,
@@ -102,41 +521,444 @@ where
         target,
         expr,
         data,
-        eq_resolver: _,
+        dialect,
+        derived,
+        missing,
+        na,
+        categorical,
+        filter,
+        eq_resolver,
+        derivatives,
+        bound,
+        fix,
+        init,
+        multistart,
+        sampling,
+        seed,
+        weights,
+        sigma,
+        loss,
+        loss_tuning,
+        confidence,
+        bootstrap,
+        resample,
+        max_iter,
+        ftol,
+        xtol,
+        gtol,
+        epsfcn,
+        step_factor,
+        compare,
+        compare_file,
+        cv,
+        group_by,
         out,
         no_stats,
+        residuals,
+        save_model,
+        covariance,
         debug,
     } = app;
 
-    let mut rdr = match &data {
-        Some(path) => data::CsvReader::new(io::BufReader::new(
-            fs::File::open(path)
+    let rdr = open_csv(data.as_ref(), &dialect)?;
+    let with_path_ctx = || path_ctx(data.as_ref());
+
+    let mut exprs = compare;
+    if let Some(path) = &compare_file {
+        let s = fs::read_to_string(path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read '{}'", path.display()))?;
+        exprs.extend(
+            s.lines()
+                .map(str::trim)
+                .filter(|x| !x.is_empty() && !x.starts_with('#'))
+                .map(String::from),
+        );
+    }
+
+    let data = data::Data::try_from(rdr).wrap_err_with(with_path_ctx)?;
+
+    // debugging inspects the equation before the data is prepared, which may fail on the bad
+    // input being debugged, so derived columns are only named
+    if debug {
+        let names = derived
+            .iter()
+            .filter_map(|x| x.split_once('=').map(|x| x.0));
+        let hdrs = Headers::from_iter(data.headers().iter().chain(names));
+        let eq = E::parse(&expr, &hdrs).wrap_err_with(with_path_ctx)?;
+        return output_debug(&eq, &hdrs, &target);
+    }
+
+    let used = used_columns::<E>(
+        data.headers(),
+        std::iter::once(&expr)
+            .chain(&exprs)
+            .map(String::as_str)
+            .chain(
+                derived
+                    .iter()
+                    .filter_map(|x| x.split_once('=').map(|x| x.1)),
+            )
+            .chain(filter.iter().flat_map(|x| filter::sides(x))),
+        std::iter::once(target.as_str()).chain(
+            [&weights, &sigma, &group_by]
+                .into_iter()
+                .filter_map(Option::as_deref),
+        ),
+    );
+    let (mut data, dropped) =
+        drop_missing(data, &used, missing, &na).wrap_err_with(with_path_ctx)?;
+    let report_dropped = || {
+        if let Missing::Drop = missing {
+            eprintln!("Dropped {dropped} rows with missing values");
+        }
+    };
+
+    derive_columns::<E>(&mut data, &derived).wrap_err_with(with_path_ctx)?;
+    let mut data = filter_data::<E>(data, &filter).wrap_err_with(with_path_ctx)?;
+    // levels are taken from the filtered rows
+    categorical_columns(&mut data, &categorical).wrap_err_with(with_path_ctx)?;
+
+    // equations are parsed against the data's headers, which know the categorical columns
+    let hdrs = data.headers();
+    let eq = E::parse(&expr, hdrs).wrap_err_with(with_path_ctx)?;
+
+    let others = exprs
+        .iter()
+        .map(|x| E::parse(x, hdrs))
+        .collect::<Result<Vec<_>>>()
+        .wrap_err_with(with_path_ctx)?;
+
+    // a saved model records the seed, so a multi-start search or bootstrap can be reproduced
+    let seed = seed.or_else(|| {
+        let random = multistart.unwrap_or_default() > 1 || bootstrap.unwrap_or_default() > 0;
+        (save_model.is_some() && random).then(|| fastrand::u64(..))
+    });
+
+    let opts = FitOptions {
+        derivatives,
+        bounds: bound,
+        fixed: fix,
+        init,
+        multistart: multistart.unwrap_or_default(),
+        sampling,
+        seed,
+        solver: SolverConfig {
+            max_iter,
+            ftol,
+            xtol,
+            gtol,
+            epsfcn,
+            step_factor,
+        },
+        weights: weights.map(Weights::Column).or(sigma.map(Weights::Sigma)),
+        loss,
+        loss_tuning,
+        confidence: Some(confidence),
+        bootstrap: bootstrap.unwrap_or_default(),
+        resample,
+    };
+
+    if let Some(k) = cv {
+        report_dropped();
+        return solve::cross_validate(&eq, &data, &target, &opts, k)
+            .wrap_err_with(with_path_ctx)?
+            .write_results(out, !no_stats, io::stdout());
+    }
+
+    if let Some(column) = group_by {
+        report_dropped();
+        return solve::fit_groups(&eq, &data, &target, &opts, &column)
+            .wrap_err_with(with_path_ctx)?
+            .write_results(out, !no_stats, io::stdout());
+    }
+
+    if compare_file.is_some() || !others.is_empty() {
+        report_dropped();
+        let eqs = std::iter::once(eq).chain(others).collect::<Vec<_>>();
+        return solve::compare(&eqs, &data, &target, &opts)
+            .wrap_err_with(with_path_ctx)?
+            .write_results(out, io::stdout());
+    }
+
+    let mut fitted = fit_with(&eq, &data, &target, &opts).wrap_err_with(with_path_ctx)?;
+    if let Missing::Drop = missing {
+        fitted.dropped_rows = Some(dropped as u64);
+    }
+
+    if let Some(path) = residuals {
+        let preds = fitted
+            .predictions(&eq, &data, &target)
+            .wrap_err_with(with_path_ctx)?;
+        let json = path
+            .extension()
+            .is_some_and(|x| x.eq_ignore_ascii_case("json"));
+        let file = fs::File::create(&path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to create '{}'", path.display()))?;
+        let values = preds.iter().map(Prediction::values).collect::<Vec<_>>();
+        solve::write_rows(
+            &data,
+            &solve::PREDICTION_COLUMNS,
+            &values,
+            json,
+            io::BufWriter::new(file),
+        )
+        .wrap_err_with(|| format!("failed to write residuals to '{}'", path.display()))?;
+    }
+
+    fitted.write_results(out, !no_stats, covariance, std::io::stdout())?;
+
+    if let Some(path) = save_model {
+        let file = fs::File::create(&path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to create '{}'", path.display()))?;
+        let mut model = Model::new(expr, target, eq_resolver, opts, fitted);
+        model.derived = derived;
+        model.filters = filter;
+        model
+            .write(io::BufWriter::new(file))
+            .wrap_err_with(|| format!("failed to write model to '{}'", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// A fit read from a file, see [`Predict::fit`].
+enum Fitted {
+    Model(Box<Model>),
+    Fit(Box<Fit>),
+}
+
+impl Fitted {
+    /// Read a saved model, or the JSON output of a fit, which has no version.
+    fn read(path: &std::path::Path) -> Result<Self> {
+        let s = fs::read_to_string(path).into_diagnostic()?;
+        let value: serde_json::Value = serde_json::from_str(&s)
+            .into_diagnostic()
+            .wrap_err("file is not valid JSON")?;
+
+        if value.get("version").is_some() {
+            Model::read(s.as_bytes()).map(|m| Fitted::Model(Box::new(m)))
+        } else {
+            serde_json::from_value(value)
                 .into_diagnostic()
-                .wrap_err_with(|| format!("failed to open '{}'", path.display()))?,
-        )),
-        None => {
-            eprintln!("Reading CSV from stdin");
-            data::CsvReader::new(io::stdin())
+                .map(|f| Fitted::Fit(Box::new(f)))
+        }
+    }
+}
+
+fn predict<E>(predict: Predict, fitted: Fitted) -> Result<()>
+where
+    E: Equation,
+{
+    let Predict {
+        fit: _,
+        data,
+        expr,
+        dialect,
+        derived,
+        eq_resolver: _,
+        bands,
+        confidence,
+        out,
+    } = predict;
+
+    // a model stores its equation, which a given equation must match
+    match (&fitted, &expr) {
+        (Fitted::Model(m), Some(x)) => {
+            let strip = |s: &str| s.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+            ensure!(
+                strip(x) == strip(&m.expr),
+                "equation '{}' does not match the model's equation '{}'",
+                x,
+                m.expr
+            );
+        }
+        (Fitted::Fit(_), None) => {
+            return Err(miette!("the fit does not store its equation, use `--expr`"));
+        }
+        _ => (),
+    }
+
+    let rdr = open_csv(data.as_ref(), &dialect)?;
+    let with_path_ctx = || path_ctx(data.as_ref());
+
+    let mut data = data::Data::try_from(rdr).wrap_err_with(with_path_ctx)?;
+    // a model's derived columns are computed before any given definitions
+    let (levels, derived) = match &fitted {
+        Fitted::Model(m) => (
+            &m.fit.levels,
+            m.derived.iter().chain(&derived).cloned().collect(),
+        ),
+        Fitted::Fit(f) => (&f.levels, derived),
+    };
+    fitted_levels(&mut data, levels).wrap_err_with(with_path_ctx)?;
+    derive_columns::<E>(&mut data, &derived).wrap_err_with(with_path_ctx)?;
+    let (eq, fitted) = match fitted {
+        Fitted::Model(m) => (m.equation::<E>(data.headers()), m.fit),
+        Fitted::Fit(f) => {
+            let expr = expr.expect("checked to be given for a fit");
+            (E::parse(&expr, data.headers()), *f)
         }
     };
+    let eq = eq.wrap_err_with(with_path_ctx)?;
 
-    let with_path_ctx = || {
-        data.as_ref()
-            .map(|p| format!("in '{}'", p.display()))
-            .unwrap_or_else(|| "from stdin".into())
+    let fcs = fitted
+        .forecast(&eq, &data, confidence)
+        .wrap_err_with(with_path_ctx)?;
+
+    let cols = if bands {
+        &solve::FORECAST_BAND_COLUMNS[..]
+    } else {
+        &solve::FORECAST_COLUMNS[..]
     };
+    let values = fcs.iter().map(|x| x.values(bands)).collect::<Vec<_>>();
+    let json = matches!(out, RowOutput::Json);
 
-    let hdrs = rdr.headers().wrap_err_with(with_path_ctx)?;
-    let eq = E::parse(&expr, hdrs).wrap_err_with(with_path_ctx)?;
+    solve::write_rows(&data, cols, &values, json, io::stdout())
+}
 
-    if debug {
-        return output_debug(&eq, hdrs, &target);
+/// The columns referenced by the expressions, along with the named columns.
+fn used_columns<'a, E: Equation>(
+    hdrs: &Headers,
+    exprs: impl Iterator<Item = &'a str>,
+    names: impl Iterator<Item = &'a str>,
+) -> Vec<usize> {
+    // expressions failing to parse here are reported when parsed against the prepared data
+    let vars = exprs
+        .filter_map(|x| E::parse(x, hdrs).ok())
+        .flat_map(|e| e.vars());
+
+    let mut cols = names
+        .map(String::from)
+        .chain(vars)
+        .filter_map(|x| hdrs.find_ignore_case_and_ws(&x))
+        .collect::<Vec<_>>();
+    cols.sort_unstable();
+    cols.dedup();
+    cols
+}
+
+/// Check the `used` columns for missing values, dropping the rows with any if the policy is to
+/// drop. Returns the data and the number of rows dropped.
+fn drop_missing(
+    data: Data,
+    used: &[usize],
+    policy: Missing,
+    na: &[String],
+) -> Result<(Data, usize)> {
+    let na = if na.is_empty() {
+        data::DEFAULT_NA.map(String::from).to_vec()
+    } else {
+        na.to_vec()
+    };
+    let missing = |row: DataRow| used.iter().copied().find(|&c| row.is_missing(c, &na));
+
+    match policy {
+        Missing::Error => {
+            if let Some((row, c)) = data.rows().find_map(|r| missing(r).map(|c| (r, c))) {
+                let name = data.headers().iter().nth(c).expect("inside headers");
+                return Err(miette!("missing value in column '{}'", name)
+                    .wrap_err(format!("in row index {}", row.idx() + 1))
+                    .wrap_err("help - use `--missing drop` to drop rows with missing values"));
+            }
+            Ok((data, 0))
+        }
+        Missing::Drop => {
+            let kept = data.filter(|row| Ok(missing(row).is_none()))?;
+            ensure!(!kept.is_empty(), "every row has missing values");
+            let dropped = data.len() - kept.len();
+            Ok((kept, dropped))
+        }
     }
+}
 
-    let data = data::Data::try_from(rdr).wrap_err_with(with_path_ctx)?;
-    let fitted = fit(eq, data, &target).wrap_err_with(with_path_ctx)?;
+/// Make each named column categorical.
+fn categorical_columns(data: &mut Data, names: &[String]) -> Result<()> {
+    for name in names {
+        let c = data
+            .headers()
+            .find_ignore_case_and_ws(name)
+            .ok_or_else(|| miette!("could not find column '{}' in headers", name))
+            .wrap_err_with(|| data::match_hdr_help(data.headers(), name))?;
+        data.set_categorical(c)?;
+    }
+    Ok(())
+}
+
+/// Make each column categorical with the levels of a fit.
+fn fitted_levels(data: &mut Data, levels: &BTreeMap<String, Vec<String>>) -> Result<()> {
+    for (name, levels) in levels {
+        let c = data
+            .headers()
+            .find_ignore_case_and_ws(name)
+            .ok_or_else(|| miette!("could not find categorical column '{}' in headers", name))
+            .wrap_err_with(|| data::match_hdr_help(data.headers(), name))?;
+        data.set_levels(c, levels.clone())?;
+    }
+    Ok(())
+}
+
+/// Append each derived column, in order.
+fn derive_columns<E: Equation>(data: &mut Data, defs: &[String]) -> Result<()> {
+    for def in defs {
+        Derived::<E>::parse(def, data.headers())?.apply(data)?;
+    }
+    Ok(())
+}
+
+/// Drop the rows not matching every filter, reporting the number dropped.
+fn filter_data<E: Equation>(data: Data, filters: &[String]) -> Result<Data> {
+    if filters.is_empty() {
+        return Ok(data);
+    }
+
+    let filters = filters
+        .iter()
+        .map(|x| Filter::<E>::parse(x, data.headers()))
+        .collect::<Result<Vec<_>>>()?;
+    let kept = data.filter(|row| {
+        for f in &filters {
+            if !f.keep(row)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    })?;
+
+    eprintln!(
+        "Filtered out {} of {} rows",
+        data.len() - kept.len(),
+        data.len()
+    );
+    ensure!(!kept.is_empty(), "every row was filtered out");
+
+    Ok(kept)
+}
+
+fn open_csv(path: Option<&PathBuf>, dialect: &Dialect) -> Result<data::CsvReader> {
+    match path {
+        Some(path) => data::CsvReader::with_dialect(
+            io::BufReader::new(
+                fs::File::open(path)
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("failed to open '{}'", path.display()))?,
+            ),
+            dialect,
+        )
+        .wrap_err_with(|| path_ctx(Some(path))),
+        None => {
+            eprintln!("Reading CSV from stdin");
+            data::CsvReader::with_dialect(io::BufReader::new(io::stdin()), dialect)
+                .wrap_err_with(|| path_ctx(None))
+        }
+    }
+}
 
-    fitted.write_results(out, !no_stats, std::io::stdout())
+fn path_ctx(path: Option<&PathBuf>) -> String {
+    path.map(|p| format!("in '{}'", p.display()))
+        .unwrap_or_else(|| "from stdin".into())
 }
 
 fn output_debug<E: Equation>(eq: &E, hdrs: &Headers, target: &str) -> Result<()> {
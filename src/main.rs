@@ -1,5 +1,9 @@
 use clap::Parser;
 
 fn main() -> miette::Result<()> {
-    fitme::App::parse().run()
+    // commands which use a fitted model are parsed separately to fitting
+    match std::env::args_os().nth(1) {
+        Some(x) if fitme::Command::is_command(&x) => fitme::Command::parse().run(),
+        _ => fitme::App::parse().run(),
+    }
 }
@@ -0,0 +1,144 @@
+//! Saving and loading fitted models.
+use super::*;
+use std::io::Read;
+
+/// A fitted model, storing everything needed to reproduce or apply the fit.
+///
+/// Models are serialised as versioned JSON. The fit's fields are flattened into the model, so a
+/// model file can be used anywhere the JSON output of a fit is expected.
+///
+/// ```rust
+/// # use fitme::*;
+/// let data = Data::new(
+///     Headers::from_iter(["x", "y"]),
+///     [[0.0, 0.9], [1.0, 3.1], [2.0, 5.0], [3.0, 7.1]],
+/// )
+/// .unwrap();
+/// let eq = expr::v1::Eq::parse("m * x + c", data.headers()).unwrap();
+/// let opts = FitOptions::default();
+/// let fit = fit_with(&eq, &data, "y", &opts).unwrap();
+///
+/// let model = Model::new("m * x + c", "y", EquationResolver::V1, opts, fit);
+/// let mut file = Vec::new();
+/// model.write(&mut file).unwrap();
+///
+/// let model = Model::read(file.as_slice()).unwrap();
+/// let eq = model.equation::<expr::v1::Eq>(data.headers()).unwrap();
+/// let forecast = model.fit.forecast(&eq, &data, 0.95).unwrap();
+/// assert!((forecast[1].predicted - 3.0).abs() < 0.1);
+/// ```
+#[derive(Serialize, Deserialize)]
+pub struct Model {
+    /// The version of the model file format.
+    pub version: u32,
+
+    /// The fitted equation.
+    pub expr: String,
+
+    /// The target column.
+    pub target: String,
+
+    /// The derived column definitions, which are applied in order before predicting.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub derived: Vec<String>,
+
+    /// The filters which selected the fitted rows. Predictions are not filtered.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<String>,
+
+    /// The version of equation resolver used to parse the equation.
+    pub eq_resolver: EquationResolver,
+
+    /// The options the fit used.
+    pub options: FitOptions,
+
+    /// The fit.
+    #[serde(flatten)]
+    pub fit: Fit,
+}
+
+impl Model {
+    /// The current version of the model file format.
+    pub const VERSION: u32 = 1;
+
+    /// Create a model of the current version, without derived columns or filters.
+    pub fn new(
+        expr: impl Into<String>,
+        target: impl Into<String>,
+        eq_resolver: EquationResolver,
+        options: FitOptions,
+        fit: Fit,
+    ) -> Self {
+        Self {
+            version: Self::VERSION,
+            expr: expr.into(),
+            target: target.into(),
+            derived: Vec::new(),
+            filters: Vec::new(),
+            eq_resolver,
+            options,
+            fit,
+        }
+    }
+
+    /// Read a model, checking that its version is supported.
+    pub fn read<R: Read>(rdr: R) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_reader(rdr)
+            .into_diagnostic()
+            .wrap_err("model file is not valid JSON")?;
+
+        let version = value
+            .get("version")
+            .ok_or_else(|| miette!("model file does not have a version"))?;
+        ensure!(
+            version.as_u64() == Some(Self::VERSION.into()),
+            "unsupported model file version {}, expecting version {}",
+            version,
+            Self::VERSION
+        );
+
+        serde_json::from_value(value)
+            .into_diagnostic()
+            .wrap_err("failed to read model file")
+    }
+
+    /// Write the model as JSON.
+    pub fn write<W: Write>(&self, wtr: W) -> Result<()> {
+        serde_json::to_writer_pretty(wtr, self).into_diagnostic()
+    }
+
+    /// Parse the model's equation against the `columns`, checking that its parameters match the
+    /// fitted parameters.
+    ///
+    /// The fitted parameter values are [`Fit::parameter_values`], in the order of
+    /// [`Equation::params`].
+    pub fn equation<E: Equation>(&self, columns: &Headers) -> Result<E> {
+        let eq = E::parse(&self.expr, columns)?;
+        ensure!(
+            eq.params() == self.fit.parameter_names,
+            "equation parameters ({}) do not match the fitted parameters ({})",
+            eq.params().join(", "),
+            self.fit.parameter_names.join(", ")
+        );
+        Ok(eq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_checked() {
+        let err = Model::read(r#"{"version":2}"#.as_bytes()).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "unsupported model file version 2, expecting version 1"
+        );
+
+        let err = Model::read(r#"{"parameter_names":[]}"#.as_bytes())
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "model file does not have a version");
+    }
+}
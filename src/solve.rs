@@ -1,54 +1,367 @@
 use super::*;
 use data::Data;
 use rmpfit::{MPError, MPFitter, MPResult};
-use serde::*;
+
+mod bootstrap;
+mod compare;
+mod cv;
+mod group;
+mod lm;
+mod multistart;
+mod params;
+mod predictions;
+mod robust;
+
+pub use bootstrap::Bootstrap;
+pub use compare::{compare, Candidate, Comparison, NestedTest};
+pub use cv::{cross_validate, CrossValidation, Fold};
+pub use group::{fit_groups, Group, Grouped};
+pub use params::{ParamBound, ParamValue};
+pub(crate) use predictions::{
+    write_rows, FORECAST_BAND_COLUMNS, FORECAST_COLUMNS, PREDICTION_COLUMNS,
+};
+pub use predictions::{Forecast, Prediction};
 
 /// The result of [`fit`].
+///
+/// Undefined (`NaN`) statistics are serialised as `null`, and read back as `NaN`.
 #[derive(Serialize, Deserialize)]
 pub struct Fit {
     /// The names of the parameters.
     pub parameter_names: Vec<String>,
     /// The fitted values of the parameters.
+    #[serde(deserialize_with = "nullable::vec")]
     pub parameter_values: Vec<f64>,
 
+    /// The levels of each categorical column, the first being the reference level.
+    ///
+    /// Predictions bind the categorical columns to these levels.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub levels: BTreeMap<String, Vec<String>>,
+
     /// Number of observations.
     pub n: u64,
 
+    /// Number of rows dropped for missing values, if rows with missing values were dropped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dropped_rows: Option<u64>,
+
     /// The Standard Error of each parameter.
+    #[serde(deserialize_with = "nullable::vec")]
     pub xerrs: Vec<f64>,
 
     /// Root Mean Squared Residual error.
+    #[serde(deserialize_with = "nullable::num")]
     pub rmsr: f64,
 
     /// Adjusted R squared value.
+    #[serde(deserialize_with = "nullable::num")]
     pub rsq: f64,
 
-    /// Each parameters t-value.
+    /// R squared value, the proportion of the target's variance explained by the fit.
+    #[serde(default = "nullable::missing", deserialize_with = "nullable::num")]
+    pub rsq_unadjusted: f64,
+
+    /// Residual degrees of freedom, the number of observations less the number of estimated
+    /// parameters. Fixed parameters, and parameters pegged at a bound, are not estimated.
+    #[serde(default = "nullable::missing", deserialize_with = "nullable::num")]
+    pub dfr: f64,
+
+    /// Sum of squared (weighted) residuals.
+    #[serde(default = "nullable::missing", deserialize_with = "nullable::num")]
+    pub ssr: f64,
+
+    /// Reduced chi-square, the sum of squared residuals per residual degree of freedom.
+    ///
+    /// When weighting by each observation's standard deviation, a value near 1 indicates the
+    /// fit is consistent with the uncertainties.
+    #[serde(default = "nullable::missing", deserialize_with = "nullable::num")]
+    pub reduced_chisq: f64,
+
+    /// F-statistic of the fit against a constant model, on one fewer than the free parameters and
+    /// the residual degrees of freedom. Not a number with a single free parameter.
+    #[serde(default = "nullable::missing", deserialize_with = "nullable::num")]
+    pub fstat: f64,
+
+    /// The p-value of the F-statistic.
+    #[serde(default = "nullable::missing", deserialize_with = "nullable::num")]
+    pub fstat_pval: f64,
+
+    /// Log-likelihood of the fit, assuming normally distributed residuals.
+    #[serde(default = "nullable::missing", deserialize_with = "nullable::num")]
+    pub log_likelihood: f64,
+
+    /// Akaike information criterion.
+    ///
+    /// The residual variance counts as an estimated parameter.
+    #[serde(default = "nullable::missing", deserialize_with = "nullable::num")]
+    pub aic: f64,
+
+    /// Akaike information criterion, corrected for small sample sizes.
+    #[serde(default = "nullable::missing", deserialize_with = "nullable::num")]
+    pub aicc: f64,
+
+    /// Bayesian information criterion.
+    #[serde(default = "nullable::missing", deserialize_with = "nullable::num")]
+    pub bic: f64,
+
+    /// Each parameters t-value, not a number if the parameter was not estimated.
+    #[serde(deserialize_with = "nullable::vec")]
     pub tvals: Vec<f64>,
+
+    /// Each parameters two-sided p-value, from the Student-t distribution with the residual
+    /// degrees of freedom.
+    #[serde(default, deserialize_with = "nullable::vec")]
+    pub pvals: Vec<f64>,
+
+    /// The confidence level of the parameter confidence intervals.
+    #[serde(default, deserialize_with = "nullable::num")]
+    pub ci_level: f64,
+
+    /// The lower limit of each parameters confidence interval.
+    #[serde(default, deserialize_with = "nullable::vec")]
+    pub ci_lower: Vec<f64>,
+
+    /// The upper limit of each parameters confidence interval.
+    #[serde(default, deserialize_with = "nullable::vec")]
+    pub ci_upper: Vec<f64>,
+
+    /// The covariance matrix of the parameters, scaled by the residual variance.
+    ///
+    /// Rows and columns are in the order of [`Fit::parameter_names`].
+    #[serde(default, deserialize_with = "nullable::matrix")]
+    pub covariance: Vec<Vec<f64>>,
+
+    /// The correlation matrix of the parameters.
+    ///
+    /// Parameters without variance (fixed or pegged at a bound) have undefined (`NaN`)
+    /// correlations.
+    #[serde(default, deserialize_with = "nullable::matrix")]
+    pub correlation: Vec<Vec<f64>>,
+
+    /// The names of parameters which were held fixed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixed_parameters: Vec<String>,
+
+    /// Number of starting points tried, if a multi-start search was used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub starts: Option<u64>,
+
+    /// Number of starting points which converged to the reported minimum, if a multi-start search
+    /// was used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub converged_starts: Option<u64>,
+
+    /// Number of solver iterations used.
+    #[serde(default)]
+    pub iterations: u64,
+
+    /// Number of function evaluations used by the solver.
+    #[serde(default)]
+    pub function_evaluations: u64,
+
+    /// Why the solver terminated.
+    #[serde(default)]
+    pub termination: String,
+
+    /// The robust loss used, if not least squares.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loss: Option<String>,
+
+    /// Number of reweighting rounds used to fit a robust loss.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub irls_rounds: Option<u64>,
+
+    /// Bootstrap estimates of the parameter uncertainties, if resampling was requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootstrap: Option<Bootstrap>,
+
+    /// The observation weights of the fit, including any robust reweighting.
+    ///
+    /// `None` if the fit is unweighted. The weights are not serialised.
+    #[serde(skip)]
+    pub weights: Option<Vec<f64>>,
 }
 
 impl Fit {
     /// Write the results of the fit in a particular output format to a writer.
+    ///
+    /// `write_covariance` writes the covariance and correlation matrices after the parameters.
+    /// The JSON output always includes the matrices.
     pub fn write_results<W: std::io::Write>(
         &self,
         output: Output,
         write_statistics: bool,
+        write_covariance: bool,
         wtr: W,
     ) -> Result<()> {
+        let (s, c) = (write_statistics, write_covariance);
         match output {
-            Output::Table => write_rich_table(self, write_statistics, wtr),
-            Output::Plain => write_plain_table(self, write_statistics, wtr),
-            Output::Csv => write_csv_table(self, write_statistics, wtr).into_diagnostic(),
-            Output::Md => write_md_table(self, write_statistics, wtr),
+            Output::Table => write_rich_table(self, s, c, wtr),
+            Output::Plain => write_plain_table(self, s, c, wtr),
+            Output::Csv => write_csv_table(self, s, c, wtr).into_diagnostic(),
+            Output::Md => write_md_table(self, s, c, wtr),
             Output::Json => write_json_table(self),
         }
     }
 }
 
-struct Fitter<E> {
-    data: Data,
-    eq: E,
+/// Options to tune [`fit_with`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FitOptions {
+    /// How the partial derivatives of the equation are calculated.
+    pub derivatives: Derivatives,
+
+    /// Parameters constrained within bounds.
+    pub bounds: Vec<ParamBound>,
+
+    /// Parameters held at a fixed value.
+    ///
+    /// Fixed parameters are not varied, but are still reported in the [`Fit`].
+    pub fixed: Vec<ParamValue>,
+
+    /// Initial values for parameters.
+    ///
+    /// Parameters without an initial value are guessed.
+    /// Initial values are clamped into any bounds, and fixed values take precedence.
+    pub init: Vec<ParamValue>,
+
+    /// Number of starting points to refine from in a multi-start search.
+    ///
+    /// The initial parameters are always the first start, `0` or `1` does a single fit.
+    /// The best fit, by residual sum of squares, is kept.
+    pub multistart: usize,
+
+    /// How the starting points of a multi-start search are sampled.
+    pub sampling: Sampling,
+
+    /// Seed for the random number generator.
+    /// If not specified, a random seed is used.
+    pub seed: Option<u64>,
+
+    /// Solver settings.
+    pub solver: SolverConfig,
+
+    /// Weight each observation's residual by a column.
+    ///
+    /// The statistics of the [`Fit`] are calculated in their weighted forms.
+    pub weights: Option<Weights>,
+
+    /// The loss function to minimise.
+    ///
+    /// Robust losses are fitted with iteratively reweighted least squares, and the statistics of
+    /// the [`Fit`] are calculated with the final weights.
+    pub loss: Loss,
+
+    /// The tuning constant of a robust loss, in units of the residuals' robust standard deviation.
+    ///
+    /// If not specified, the constant giving 95% efficiency for normal residuals is used.
+    pub loss_tuning: Option<f64>,
+
+    /// The confidence level of the parameter confidence intervals, between 0 and 1.
+    ///
+    /// Defaults to 0.95.
+    pub confidence: Option<f64>,
+
+    /// Number of bootstrap resamples to estimate the parameter uncertainties with.
+    ///
+    /// `0` skips the bootstrap. The resamples are drawn from the random number generator seeded
+    /// with [`FitOptions::seed`].
+    pub bootstrap: usize,
+
+    /// What the bootstrap resamples.
+    pub resample: Resample,
+}
+
+/// A column to weight each observation's residual by.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Weights {
+    /// The column holds the weight of each observation.
+    Column(String),
+
+    /// The column holds the standard deviation (uncertainty) of each observation.
+    /// The weights are the inverse variances `1 / sigma^2`.
+    Sigma(String),
+}
+
+/// Settings to tune the Levenberg-Marquardt solver.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolverConfig {
+    /// Maximum number of iterations.
+    pub max_iter: usize,
+
+    /// Relative tolerance of the residual sum of squares for convergence.
+    pub ftol: f64,
+
+    /// Relative tolerance of the parameter values for convergence.
+    pub xtol: f64,
+
+    /// Orthogonality tolerance for convergence.
+    pub gtol: f64,
+
+    /// Relative step size used to approximate derivatives with finite differences.
+    ///
+    /// Only used with [`Derivatives::Numeric`].
+    pub epsfcn: f64,
+
+    /// Initial step bound, as a factor of the parameter magnitudes.
+    ///
+    /// Only used with [`Derivatives::Numeric`].
+    pub step_factor: f64,
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        let rmpfit::MPConfig {
+            ftol,
+            xtol,
+            gtol,
+            epsfcn,
+            step_factor,
+            ..
+        } = Default::default();
+
+        Self {
+            max_iter: 3000,
+            ftol,
+            xtol,
+            gtol,
+            epsfcn,
+            step_factor,
+        }
+    }
+}
+
+impl SolverConfig {
+    fn mpconfig(&self) -> rmpfit::MPConfig {
+        let Self {
+            max_iter,
+            ftol,
+            xtol,
+            gtol,
+            epsfcn,
+            step_factor,
+        } = *self;
+
+        rmpfit::MPConfig {
+            max_iter,
+            ftol,
+            xtol,
+            gtol,
+            epsfcn,
+            step_factor,
+            ..Default::default()
+        }
+    }
+}
+
+struct Fitter<'a, E> {
+    data: &'a Data,
+    eq: &'a E,
     tgt: usize,
+    /// Square root of the observation weights.
+    sqrt_wts: Option<Vec<f64>>,
 }
 
 /// Fit an equation using the input data.
@@ -111,24 +424,47 @@ struct Fitter<E> {
 ///
 /// assert_eq!(fit.n, 10);
 /// assert_eq!(&fit.parameter_names, &["c".to_string(), "m".to_string()]);
-/// assert_eq!(&fit.parameter_values, &[3.2099657167997013, 1.7709542029456211]);
+/// assert_eq!(&fit.parameter_values, &[3.2099657168276927, 1.7709542026534584]);
 /// ```
 pub fn fit<E: Equation>(eq: E, data: Data, target: &str) -> Result<Fit> {
+    fit_with(&eq, &data, target, &FitOptions::default())
+}
+
+/// Fit an equation using the input data, with the given options.
+///
+/// See [`fit`] for more details.
+pub fn fit_with<E: Equation>(eq: &E, data: &Data, target: &str, opts: &FitOptions) -> Result<Fit> {
     let tgt = data
         .headers()
         .find_ignore_case_and_ws(target)
         .ok_or_else(|| miette!("could not find column '{}' in headers", target))
         .wrap_err_with(|| data::match_hdr_help(data.headers(), target))?;
 
-    ensure_float_values_in_data(&eq, &data, tgt)?;
-
-    let fitter = Fitter { data, eq, tgt };
-
-    // we try to guess a set of params that can work
-    let mut params =
-        guess_params(&fitter.data, &fitter.eq).unwrap_or_else(|| vec![0.1; fitter.eq.params_len()]);
+    ensure_float_values_in_data(eq, data, tgt)?;
+    let levels = categorical_levels(eq, data)?;
+
+    let ci_level = opts.confidence.unwrap_or(0.95);
+    ensure!(
+        ci_level > 0.0 && ci_level < 1.0,
+        "confidence level must be between 0 and 1, found {}",
+        ci_level
+    );
+
+    let wts = opts
+        .weights
+        .as_ref()
+        .map(|w| read_weights(data, w))
+        .transpose()?;
+    let sqrt_wts = wts.as_ref().map(|w| w.iter().map(|w| w.sqrt()).collect());
+
+    let mut fitter = Fitter {
+        data,
+        eq,
+        tgt,
+        sqrt_wts,
+    };
 
-    if params.is_empty() {
+    if fitter.eq.params_len() == 0 {
         let mut x = Err(miette!("equation has 0 parameters to fit")).wrap_err(
             "equation must have a least one variable which does not match a column header",
         );
@@ -139,55 +475,137 @@ pub fn fit<E: Equation>(eq: E, data: Data, target: &str) -> Result<Fit> {
         return x;
     }
 
-    let config = rmpfit::MPConfig {
-        max_iter: 3000,
-        ..Default::default()
+    let names = fitter.eq.params();
+    let init = params::initial_values(&names, opts)?;
+    let constraints = params::Constraints::new(&names, opts)?;
+    let constrain = |x: &mut [f64]| {
+        for &(i, v) in &init {
+            x[i] = v;
+        }
+        if let Some(c) = &constraints {
+            c.apply(x);
+        }
     };
 
-    let status = fitter
-        .mpfit(&mut params, None, &config)
-        .map_err(|e| miette!("{}", e))
-        .wrap_err("failed to fit the equation to the input data")?;
+    // we try to guess a set of params that can work, keeping any initial values supplied
+    let mut params = guess_params(fitter.data, fitter.eq, constrain).unwrap_or_else(|| {
+        let mut x = vec![0.1; fitter.eq.params_len()];
+        constrain(&mut x);
+        x
+    });
+
+    let config = opts.solver.mpconfig();
+
+    let analytic = match opts.derivatives {
+        Derivatives::Analytic => fitter.has_jacobian(&params),
+        Derivatives::Numeric => false,
+    };
 
-    let Fitter { data, eq, tgt } = fitter;
+    let pars = constraints.as_ref().map(|c| c.pars.as_slice());
+    let solve = |fitter: &Fitter<'_, E>, x: &mut [f64]| {
+        if analytic {
+            lm::lmfit(fitter, x, pars, &config)
+        } else {
+            fitter.mpfit(x, pars, &config)
+        }
+    };
+
+    let (status, starts, converged_starts) = if opts.multistart > 1 {
+        let mut rng = opts.seed.map(fastrand::Rng::with_seed).unwrap_or_default();
+        let starts = multistart::starts(&params, pars, opts.multistart, opts.sampling, &mut rng);
+        let n = starts.len() as u64;
+        let search = multistart::search(starts, |x| solve(&fitter, x));
+        search.map(|s| {
+            params = s.params;
+            (s.status, Some(n), Some(s.converged))
+        })
+    } else {
+        solve(&fitter, &mut params).map(|s| (s, None, None))
+    }
+    .map_err(|e| miette!("{}", e))
+    .wrap_err("failed to fit the equation to the input data")?;
+
+    // robust losses reweight the observations, starting from the least squares fit
+    let mut wts = wts;
+    let mut status = status;
+    let irls_rounds = if matches!(opts.loss, Loss::Squared) {
+        None
+    } else {
+        let c = opts
+            .loss_tuning
+            .unwrap_or_else(|| robust::tuning(opts.loss));
+        ensure!(
+            c > 0.0,
+            "loss tuning constant must be positive, found {}",
+            c
+        );
+        let base = wts.clone();
+        let mut resid = vec![0.0; fitter.data.len()];
+        let mut rounds = 0;
+        while rounds < robust::MAX_ROUNDS {
+            fitter.residuals(&params, &mut resid)?;
+            let Some(w) = robust::weights(opts.loss, c, &resid, base.as_deref()) else {
+                break;
+            };
+            fitter.sqrt_wts = Some(w.iter().map(|w| w.sqrt()).collect());
+            wts = Some(w);
+
+            let prev = params.clone();
+            status = solve(&fitter, &mut params)
+                .map_err(|e| miette!("{}", e))
+                .wrap_err("failed to fit the equation to the input data")
+                .wrap_err_with(|| format!("in reweighting round {}", rounds + 1))?;
+            rounds += 1;
+
+            if robust::converged(&prev, &params) {
+                break;
+            }
+        }
+        Some(rounds as u64)
+    };
+
+    let fixed_parameters = names
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| constraints.as_ref().is_some_and(|c| c.is_fixed(*i)))
+        .map(|(_, p)| p.clone())
+        .collect::<Vec<_>>();
 
     let n = data.len() as f64;
-    let k = params.len() as f64;
+    // fixed parameters, and those pegged at a bound or linearly dependent, have no variance and
+    // are not estimated
+    let estimated = status.xerror.iter().map(|&x| x > 0.0).collect::<Vec<_>>();
+    let k = estimated.iter().filter(|&&x| x).count() as f64;
+
+    let y = data.num_col(tgt).expect("inside data").expect("is number");
+
+    // Observation weights, unweighted is all ones.
+    let weights = wts;
+    let wts = weights.clone().unwrap_or_else(|| vec![1.0; data.len()]);
 
-    let mean_y = data
-        .rows()
-        .map(|row| row.get_num(tgt).expect("inside data").expect("is number"))
-        .sum::<f64>()
-        / n;
+    let mean_y = y.iter().zip(&wts).map(|(y, w)| w * y).sum::<f64>() / wts.iter().sum::<f64>();
 
     // Y predicition from regression.
-    let y_pred: Vec<f64> = data
-        .rows()
-        .map(|row| eq.solve(&params, row))
-        .try_fold(Vec::new(), |mut x, y| {
-            y.map(|y| {
-                x.push(y);
-                x
-            })
-        })
+    let mut y_pred = vec![0.0; data.len()];
+    eq.solve_batch(&params, data, &mut y_pred)
         .ok_or_else(|| miette!("failed to solve equation when summarising"))?;
 
-    // Degrees of Freedom Residual
-    let dfr = n - k - 1.;
+    // Degrees of Freedom Residual, the observations less the free parameters, as in R's `lm`
+    let dfr = n - k;
 
     // Sum of Square Residuals
-    let ssr = data
-        .rows()
+    let ssr = y
+        .iter()
         .zip(&y_pred)
-        .map(|(row, y_)| row.get_num(tgt).expect("inside data").expect("is number") - y_)
-        .map(|x| x.powi(2))
+        .zip(&wts)
+        .map(|((y, y_), w)| w * (y - y_).powi(2))
         .sum::<f64>();
 
     // Sum of Squares Explained
     let sse = y_pred
         .into_iter()
-        .map(|y| y - mean_y)
-        .map(|x| x.powi(2))
+        .zip(&wts)
+        .map(|(y, w)| w * (y - mean_y).powi(2))
         .sum::<f64>();
 
     // Root Mean Squared Residual
@@ -196,10 +614,33 @@ pub fn fit<E: Equation>(eq: E, data: Data, target: &str) -> Result<Fit> {
     // Sum of Squares Total
     let sst = sse + ssr;
 
-    let rsq = 1. - ssr / sst;
+    let rsq_unadjusted = 1. - ssr / sst;
 
     // Adjusted R squared.
-    let rsq = 1. - (1. - rsq) * (n - 1.) / dfr;
+    let rsq = 1. - (1. - rsq_unadjusted) * (n - 1.) / dfr;
+
+    let reduced_chisq = ssr / dfr;
+
+    // F-statistic against the constant model, the explained variance per free parameter beyond
+    // the constant over the residual variance
+    let dfm = k - 1.;
+    let fstat = if dfm > 0. {
+        (sse / dfm) / reduced_chisq
+    } else {
+        f64::NAN
+    };
+    let fstat_pval = stats::f_pvalue(fstat, dfm, dfr);
+
+    // Gaussian log-likelihood at the maximum likelihood residual variance.
+    // The weights are relative inverse variances, which scale the likelihood of each observation.
+    let log_likelihood = -0.5 * n * ((2. * std::f64::consts::PI * ssr / n).ln() + 1.)
+        + 0.5 * wts.iter().filter(|&&w| w > 0.).map(|w| w.ln()).sum::<f64>();
+
+    // information criteria count the residual variance as a parameter
+    let p = k + 1.;
+    let aic = 2. * p - 2. * log_likelihood;
+    let aicc = aic + 2. * p * (p + 1.) / (n - p - 1.);
+    let bic = p * n.ln() - 2. * log_likelihood;
 
     // rmpfit seems to give the sqrt of the Cjj number.
     // multiplying this by the rmsr gives a std error which matches R lm function
@@ -209,53 +650,197 @@ pub fn fit<E: Equation>(eq: E, data: Data, target: &str) -> Result<Fit> {
         .map(|x| x * rmsr)
         .collect::<Vec<_>>();
 
+    // the covariance is scaled by the residual variance, in the same way as the std errors
+    let np = params.len();
+    let covariance = status
+        .covar
+        .chunks_exact(np)
+        .map(|r| r.iter().map(|c| c * rmsr * rmsr).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let correlation = (0..np)
+        .map(|i| {
+            (0..np)
+                .map(|j| {
+                    let d = (covariance[i][i] * covariance[j][j]).sqrt();
+                    if d > 0.0 {
+                        covariance[i][j] / d
+                    } else {
+                        f64::NAN
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    // parameters which are not estimated have undefined statistics
     let tvals = params
         .iter()
         .zip(&xerrs)
-        .map(|(co, er)| co / er)
+        .zip(&estimated)
+        .map(|((co, er), &est)| if est { co / er } else { f64::NAN })
+        .collect::<Vec<_>>();
+
+    let pvals = tvals
+        .iter()
+        .map(|&t| stats::t_pvalue(t, dfr))
         .collect::<Vec<_>>();
 
-    Ok(Fit {
-        parameter_names: eq.params(),
+    // critical t-value of the two-sided interval
+    let tcrit = stats::t_quantile(0.5 + ci_level / 2.0, dfr);
+    let ci = |sign: f64| {
+        params
+            .iter()
+            .zip(&xerrs)
+            .zip(&estimated)
+            .map(|((co, er), &est)| {
+                if est {
+                    co + sign * tcrit * er
+                } else {
+                    f64::NAN
+                }
+            })
+            .collect()
+    };
+    let ci_lower = ci(-1.0);
+    let ci_upper = ci(1.0);
+
+    let mut fit = Fit {
+        parameter_names: names,
         parameter_values: params,
+        levels,
         n: data.len() as u64,
+        dropped_rows: None,
         xerrs,
         rmsr,
         rsq,
+        rsq_unadjusted,
+        dfr,
+        ssr,
+        reduced_chisq,
+        fstat,
+        fstat_pval,
+        log_likelihood,
+        aic,
+        aicc,
+        bic,
         tvals,
-    })
+        pvals,
+        ci_level,
+        ci_lower,
+        ci_upper,
+        covariance,
+        correlation,
+        fixed_parameters,
+        starts,
+        converged_starts,
+        iterations: status.n_iter as u64,
+        function_evaluations: status.n_fev as u64,
+        termination: status.success.to_string(),
+        loss: irls_rounds.map(|_| {
+            opts.loss
+                .to_possible_value()
+                .expect("not skipped")
+                .get_name()
+                .to_string()
+        }),
+        irls_rounds,
+        bootstrap: None,
+        weights,
+    };
+
+    if opts.bootstrap > 0 {
+        fit.bootstrap = Some(bootstrap::run(eq, data, target, opts, &fit)?);
+    }
+
+    Ok(fit)
 }
 
-impl<E: Equation> MPFitter for Fitter<E> {
+impl<E: Equation> MPFitter for Fitter<'_, E> {
     fn number_of_points(&self) -> usize {
         self.data.len()
     }
 
     fn eval(&self, params: &[f64], deviates: &mut [f64]) -> MPResult<()> {
-        for (d, row) in deviates.iter_mut().zip(self.data.rows()) {
-            let f = self.eq.solve(params, row).ok_or(MPError::Eval)?;
-
-            if f.is_finite() {
-                let y = row
-                    .get_num(self.tgt)
-                    .expect("inside data")
-                    .expect("is number");
-                *d = y - f;
+        self.eq
+            .solve_batch(params, self.data, deviates)
+            .ok_or(MPError::Eval)?;
+
+        let ys = self
+            .data
+            .num_col(self.tgt)
+            .expect("inside data")
+            .expect("is number");
+
+        for (d, y) in deviates.iter_mut().zip(ys) {
+            if d.is_finite() {
+                *d = y - *d;
             } else {
                 *d = 1e13; // very large deviation
             }
         }
 
+        if let Some(sw) = &self.sqrt_wts {
+            deviates.iter_mut().zip(sw).for_each(|(d, w)| *d *= w);
+        }
+
+        Ok(())
+    }
+}
+
+impl<E: Equation> lm::Jacobian for Fitter<'_, E> {
+    fn jacobian(&self, params: &[f64], jac: &mut [f64]) -> MPResult<()> {
+        self.eq
+            .jacobian_batch(params, self.data, jac)
+            .ok_or(MPError::Eval)?;
+
+        // deviates are y - f, so the derivatives are negated
+        // non-finite derivatives do not contribute to the step
+        for d in jac.iter_mut() {
+            *d = if d.is_finite() { -*d } else { 0.0 };
+        }
+
+        if let Some(sw) = &self.sqrt_wts {
+            let n = params.len();
+            for (row, w) in jac.chunks_exact_mut(n).zip(sw) {
+                row.iter_mut().for_each(|d| *d *= w);
+            }
+        }
+
         Ok(())
     }
 }
 
+impl<E: Equation> Fitter<'_, E> {
+    /// Calculate the unweighted residuals `y - f`.
+    fn residuals(&self, params: &[f64], resid: &mut [f64]) -> Result<()> {
+        self.eq
+            .solve_batch(params, self.data, resid)
+            .ok_or_else(|| miette!("failed to solve equation when calculating residuals"))?;
+
+        let ys = self
+            .data
+            .num_col(self.tgt)
+            .expect("inside data")
+            .expect("is number");
+
+        resid.iter_mut().zip(ys).for_each(|(r, y)| *r = y - *r);
+
+        Ok(())
+    }
+
+    /// Does the equation supply analytic derivatives?
+    fn has_jacobian(&self, params: &[f64]) -> bool {
+        let mut jac = vec![0.0; self.data.len() * params.len()];
+        self.eq
+            .jacobian_batch(params, self.data, &mut jac)
+            .is_some()
+    }
+}
+
 fn ensure_float_values_in_data<E: Equation>(eq: &E, data: &Data, tgt: usize) -> Result<()> {
     fn chk_col(d: &Data, c: usize) -> Result<()> {
-        for r in d.rows() {
-            r.get_num(c)
-                .ok_or_else(|| miette!("column index {} not in table", c))??;
-        }
+        d.num_col(c)
+            .ok_or_else(|| miette!("column index {} not in table", c))??;
         Ok(())
     }
 
@@ -273,26 +858,102 @@ fn ensure_float_values_in_data<E: Equation>(eq: &E, data: &Data, tgt: usize) ->
     Ok(())
 }
 
-fn guess_params<E: Equation>(data: &Data, eq: &E) -> Option<Vec<f64>> {
+/// The levels of the equation's categorical columns, each of which must be observed.
+fn categorical_levels<E: Equation>(eq: &E, data: &Data) -> Result<BTreeMap<String, Vec<String>>> {
+    let hdrs = data.headers();
+    let mut levels = BTreeMap::new();
+    for p in eq.categorical() {
+        let c = hdrs
+            .find_ignore_case_and_ws(&p)
+            .ok_or_else(|| miette!("could not find column '{}' in headers", p))?;
+        let name = hdrs.iter().nth(c).expect("column in headers");
+        let ls = hdrs
+            .levels(c)
+            .ok_or_else(|| miette!("column '{}' is not categorical", name))?;
+        for l in ls {
+            ensure!(
+                data.rows().any(|r| r.get_txt(c) == Some(l)),
+                "categorical column '{}' has no observations of level '{}'",
+                name,
+                l
+            );
+        }
+        levels.insert(name.to_string(), ls.to_vec());
+    }
+
+    Ok(levels)
+}
+
+/// Read the observation weights from the data.
+fn read_weights(data: &Data, weights: &Weights) -> Result<Vec<f64>> {
+    let (name, sigma) = match weights {
+        Weights::Column(c) => (c, false),
+        Weights::Sigma(c) => (c, true),
+    };
+
+    let c = data
+        .headers()
+        .find_ignore_case_and_ws(name)
+        .ok_or_else(|| miette!("could not find column '{}' in headers", name))
+        .wrap_err_with(|| data::match_hdr_help(data.headers(), name))?;
+    let xs = data
+        .num_col(c)
+        .ok_or_else(|| miette!("column index {} not in table", c))??;
+
+    xs.iter()
+        .enumerate()
+        .map(|(i, &x)| {
+            if sigma {
+                ensure!(
+                    x.is_finite() && x > 0.0,
+                    "sigma must be positive, found {} in row index {}",
+                    x,
+                    i + 1
+                );
+                Ok(x.powi(-2))
+            } else {
+                ensure!(
+                    x.is_finite() && x >= 0.0,
+                    "weight must be non-negative, found {} in row index {}",
+                    x,
+                    i + 1
+                );
+                Ok(x)
+            }
+        })
+        .collect::<Result<_>>()
+        .wrap_err_with(|| format!("in weights column '{name}'"))
+}
+
+fn guess_params<E, F>(data: &Data, eq: &E, constrain: F) -> Option<Vec<f64>>
+where
+    E: Equation,
+    F: Fn(&mut [f64]),
+{
     let r = data.rows().next()?;
     let mut ps = vec![0.0; eq.params_len()];
 
-    if eq.solve(&ps, r).map(|x| x.is_finite()).unwrap_or_default() {
+    let check = |ps: &mut [f64]| {
+        constrain(ps);
+        eq.solve(ps, r).map(|x| x.is_finite()).unwrap_or_default()
+    };
+
+    if check(&mut ps) {
         return Some(ps);
     }
 
     ps.fill(1.0);
-    if eq.solve(&ps, r).map(|x| x.is_finite()).unwrap_or_default() {
+    if check(&mut ps) {
         return Some(ps);
     }
 
     ps.fill(0.5);
-    if eq.solve(&ps, r).map(|x| x.is_finite()).unwrap_or_default() {
+    if check(&mut ps) {
         return Some(ps);
     }
 
     ps.iter_mut().enumerate().for_each(|(i, x)| *x = i as f64);
-    if eq.solve(&ps, r).map(|x| x.is_finite()).unwrap_or_default() {
+    if check(&mut ps) {
         return Some(ps);
     }
 
@@ -303,99 +964,183 @@ fn nfmtr() -> numfmt::Formatter {
     "[~4]".parse::<numfmt::Formatter>().expect("just fine")
 }
 
-fn write_rich_table(x: &Fit, write_stats: bool, w: impl Write) -> Result<()> {
+fn write_rich_table(x: &Fit, write_stats: bool, write_cov: bool, w: impl Write) -> Result<()> {
     write_table(
         x,
         write_stats,
+        write_cov,
         comfy_table::presets::UTF8_HORIZONTAL_ONLY,
         w,
     )
     .into_diagnostic()
 }
 
-fn write_plain_table(x: &Fit, write_stats: bool, w: impl Write) -> Result<()> {
-    write_table(x, write_stats, comfy_table::presets::NOTHING, w).into_diagnostic()
+fn write_plain_table(x: &Fit, write_stats: bool, write_cov: bool, w: impl Write) -> Result<()> {
+    write_table(x, write_stats, write_cov, comfy_table::presets::NOTHING, w).into_diagnostic()
 }
 
-fn write_csv_table(x: &Fit, write_stats: bool, mut wtr: impl Write) -> io::Result<()> {
+fn write_csv_table(
+    x: &Fit,
+    write_stats: bool,
+    write_cov: bool,
+    mut wtr: impl Write,
+) -> io::Result<()> {
     let Fit {
         parameter_names,
         parameter_values,
-        n,
-        rmsr,
-        rsq,
         xerrs,
         tvals,
+        pvals,
+        ci_level,
+        ci_lower,
+        ci_upper,
+        fixed_parameters,
+        ..
     } = x;
 
     let mut nfmtr = nfmtr();
 
     let mut w = csv::Writer::from_writer(&mut wtr);
 
-    w.write_record(["Parameter", "Value", "Standard Error", "t-value"])?;
-
-    for (((p, v), e), t) in parameter_names
-        .iter()
-        .zip(parameter_values)
-        .zip(xerrs)
-        .zip(tvals)
-    {
+    let [lo, hi] = ci_headers(*ci_level);
+    w.write_record([
+        "Parameter",
+        "Value",
+        "Standard Error",
+        "t-value",
+        "p-value",
+        &lo,
+        &hi,
+    ])?;
+
+    for (i, p) in parameter_names.iter().enumerate() {
+        let fixed = fixed_parameters.contains(p);
+        let stat = |x: &[f64]| {
+            if fixed || x[i].is_nan() {
+                String::new()
+            } else {
+                x[i].to_string()
+            }
+        };
         w.write_field(p)?;
-        w.write_field(v.to_string())?;
-        w.write_field(e.to_string())?;
-        w.write_field(t.to_string())?;
+        w.write_field(parameter_values[i].to_string())?;
+        w.write_field(if fixed { "fixed".into() } else { stat(xerrs) })?;
+        w.write_field(stat(tvals))?;
+        w.write_field(stat(pvals))?;
+        w.write_field(stat(ci_lower))?;
+        w.write_field(stat(ci_upper))?;
         w.write_record(None::<&[u8]>)?;
     }
 
     drop(w);
 
+    if let Some(b) = &x.bootstrap {
+        writeln!(wtr)?;
+        let mut w = csv::Writer::from_writer(&mut wtr);
+        w.write_record(["Parameter", "Bootstrap SE", &lo, &hi])?;
+        for (i, row) in bootstrap_rows(x, b).into_iter().enumerate() {
+            w.write_field(&parameter_names[i])?;
+            w.write_record(
+                row.iter()
+                    .map(|x| x.map(|x| x.to_string()).unwrap_or_default()),
+            )?;
+        }
+    }
+
+    if write_cov {
+        for (name, m) in [
+            ("Covariance", &x.covariance),
+            ("Correlation", &x.correlation),
+        ] {
+            writeln!(wtr)?;
+            let mut w = csv::Writer::from_writer(&mut wtr);
+            w.write_field(name)?;
+            w.write_record(parameter_names)?;
+            for (i, row) in matrix_rows(x, m).into_iter().enumerate() {
+                w.write_field(&parameter_names[i])?;
+                w.write_record(
+                    row.iter()
+                        .map(|x| x.map(|x| x.to_string()).unwrap_or_default()),
+                )?;
+            }
+        }
+    }
+
     if write_stats {
-        writeln!(&mut wtr, "  Number of observations: {}", nfmtr.fmt2(*n))?;
-        writeln!(
-            &mut wtr,
-            "  Root Mean Squared Residual error: {}",
-            nfmtr.fmt2(*rmsr)
-        )?;
-        writeln!(&mut wtr, "  R-sq Adjusted: {}", nfmtr.fmt2(*rsq))?;
+        write_stats_lines(x, &mut nfmtr, wtr)?;
     }
 
     Ok(())
 }
 
-fn write_md_table(x: &Fit, write_stats: bool, w: impl Write) -> Result<()> {
-    write_table(x, write_stats, comfy_table::presets::ASCII_MARKDOWN, w).into_diagnostic()
+fn write_md_table(x: &Fit, write_stats: bool, write_cov: bool, w: impl Write) -> Result<()> {
+    write_table(
+        x,
+        write_stats,
+        write_cov,
+        comfy_table::presets::ASCII_MARKDOWN,
+        w,
+    )
+    .into_diagnostic()
 }
 
-fn write_table(x: &Fit, write_stats: bool, table_fmt: &str, mut w: impl Write) -> io::Result<()> {
+fn write_table(
+    x: &Fit,
+    write_stats: bool,
+    write_cov: bool,
+    table_fmt: &str,
+    mut w: impl Write,
+) -> io::Result<()> {
     use comfy_table::{Cell, CellAlignment as CA, Row, Table};
 
     let Fit {
         parameter_names,
         parameter_values,
-        n,
-        rmsr,
-        rsq,
         xerrs,
         tvals,
+        pvals,
+        ci_level,
+        ci_lower,
+        ci_upper,
+        fixed_parameters,
+        ..
     } = x;
 
     let mut nfmtr = nfmtr();
 
     let mut table = Table::new();
 
-    table.set_header(["Parameter", "Value", "Standard Error", "t-value"]);
-
-    for (((p, v), e), t) in parameter_names
-        .iter()
-        .zip(parameter_values)
-        .zip(xerrs)
-        .zip(tvals)
-    {
+    let [lo, hi] = ci_headers(*ci_level);
+    table.set_header([
+        "Parameter",
+        "Value",
+        "Standard Error",
+        "t-value",
+        "p-value",
+        &lo,
+        &hi,
+    ]);
+
+    for (i, p) in parameter_names.iter().enumerate() {
+        let fixed = fixed_parameters.contains(p);
+        let stat = |x: &[f64]| Some(x[i]).filter(|x| !fixed && !x.is_nan());
+        let mut cell = |x: Option<f64>| {
+            let s = x.map(|x| nfmtr.fmt2(x).to_string()).unwrap_or_default();
+            Cell::new(s).set_alignment(CA::Right)
+        };
+        let e = if fixed {
+            Cell::new("fixed").set_alignment(CA::Right)
+        } else {
+            cell(stat(xerrs))
+        };
         let mut row = Row::new();
         row.add_cell(Cell::new(p))
-            .add_cell(Cell::new(nfmtr.fmt2(*v)).set_alignment(CA::Right))
-            .add_cell(Cell::new(nfmtr.fmt2(*e)).set_alignment(CA::Right))
-            .add_cell(Cell::new(nfmtr.fmt2(*t)).set_alignment(CA::Right));
+            .add_cell(cell(Some(parameter_values[i])))
+            .add_cell(e)
+            .add_cell(cell(stat(tvals)))
+            .add_cell(cell(stat(pvals)))
+            .add_cell(cell(stat(ci_lower)))
+            .add_cell(cell(stat(ci_upper)));
         table.add_row(row);
     }
 
@@ -403,14 +1148,159 @@ fn write_table(x: &Fit, write_stats: bool, table_fmt: &str, mut w: impl Write) -
 
     writeln!(w, "{table}")?;
 
+    if let Some(b) = &x.bootstrap {
+        let mut table = Table::new();
+        table.set_header(["Parameter", "Bootstrap SE", &lo, &hi]);
+        for (i, row) in bootstrap_rows(x, b).into_iter().enumerate() {
+            let mut r = Row::new();
+            r.add_cell(Cell::new(&parameter_names[i]));
+            for c in row {
+                let s = c.map(|c| nfmtr.fmt2(c).to_string()).unwrap_or_default();
+                r.add_cell(Cell::new(s).set_alignment(CA::Right));
+            }
+            table.add_row(r);
+        }
+        table.load_preset(table_fmt);
+        writeln!(w)?;
+        writeln!(w, "{table}")?;
+    }
+
+    if write_cov {
+        for (name, m) in [
+            ("Covariance", &x.covariance),
+            ("Correlation", &x.correlation),
+        ] {
+            let mut table = Table::new();
+            table.set_header(
+                std::iter::once(name).chain(parameter_names.iter().map(|p| p.as_str())),
+            );
+            for (i, row) in matrix_rows(x, m).into_iter().enumerate() {
+                let mut r = Row::new();
+                r.add_cell(Cell::new(&parameter_names[i]));
+                for c in row {
+                    let s = c.map(|c| nfmtr.fmt2(c).to_string()).unwrap_or_default();
+                    r.add_cell(Cell::new(s).set_alignment(CA::Right));
+                }
+                table.add_row(r);
+            }
+            table.load_preset(table_fmt);
+            writeln!(w)?;
+            writeln!(w, "{table}")?;
+        }
+    }
+
     if write_stats {
-        writeln!(w, "  Number of observations: {}", nfmtr.fmt2(*n))?;
+        write_stats_lines(x, &mut nfmtr, w)?;
+    }
+
+    Ok(())
+}
+
+/// The rows of a parameter matrix, with fixed parameters blanked.
+fn matrix_rows(x: &Fit, m: &[Vec<f64>]) -> Vec<Vec<Option<f64>>> {
+    let fixed = |i: usize| x.fixed_parameters.contains(&x.parameter_names[i]);
+    m.iter()
+        .enumerate()
+        .map(|(i, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(j, &c)| Some(c).filter(|_| !fixed(i) && !fixed(j)))
+                .collect()
+        })
+        .collect()
+}
+
+/// The bootstrap standard error and confidence limits of each parameter, blank if fixed.
+fn bootstrap_rows(x: &Fit, b: &Bootstrap) -> Vec<[Option<f64>; 3]> {
+    x.parameter_names
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            if x.fixed_parameters.contains(p) {
+                [None; 3]
+            } else {
+                [Some(b.xerrs[i]), Some(b.ci_lower[i]), Some(b.ci_upper[i])]
+            }
+        })
+        .collect()
+}
+
+/// The headers of the confidence interval limits, eg `95% CI Lower`.
+fn ci_headers(level: f64) -> [String; 2] {
+    // rounding removes floating point noise, eg 0.95 * 100 = 95.00000000000001
+    let pct = (level * 1e6).round() / 1e4;
+    [format!("{pct}% CI Lower"), format!("{pct}% CI Upper")]
+}
+
+fn write_stats_lines(x: &Fit, nfmtr: &mut numfmt::Formatter, mut w: impl Write) -> io::Result<()> {
+    let Fit {
+        n,
+        dropped_rows,
+        rmsr,
+        rsq,
+        rsq_unadjusted,
+        dfr,
+        ssr,
+        reduced_chisq,
+        fstat,
+        fstat_pval,
+        log_likelihood,
+        aic,
+        aicc,
+        bic,
+        starts,
+        converged_starts,
+        iterations,
+        function_evaluations,
+        termination,
+        loss,
+        irls_rounds,
+        bootstrap,
+        ..
+    } = x;
+
+    writeln!(w, "  Number of observations: {}", nfmtr.fmt2(*n))?;
+    if let Some(d) = dropped_rows {
+        writeln!(w, "  Dropped rows with missing values: {d}")?;
+    }
+    writeln!(w, "  Residual degrees of freedom: {}", nfmtr.fmt2(*dfr))?;
+    writeln!(w, "  Sum of Squared Residuals: {}", nfmtr.fmt2(*ssr))?;
+    writeln!(
+        w,
+        "  Root Mean Squared Residual error: {}",
+        nfmtr.fmt2(*rmsr)
+    )?;
+    writeln!(w, "  Reduced Chi-sq: {}", nfmtr.fmt2(*reduced_chisq))?;
+    writeln!(w, "  R-sq: {}", nfmtr.fmt2(*rsq_unadjusted))?;
+    writeln!(w, "  R-sq Adjusted: {}", nfmtr.fmt2(*rsq))?;
+    writeln!(w, "  F-statistic: {}", nfmtr.fmt2(*fstat))?;
+    writeln!(w, "  F-statistic p-value: {}", nfmtr.fmt2(*fstat_pval))?;
+    writeln!(w, "  Log-likelihood: {}", nfmtr.fmt2(*log_likelihood))?;
+    writeln!(w, "  AIC: {}", nfmtr.fmt2(*aic))?;
+    writeln!(w, "  AICc: {}", nfmtr.fmt2(*aicc))?;
+    writeln!(w, "  BIC: {}", nfmtr.fmt2(*bic))?;
+    if let (Some(s), Some(c)) = (starts, converged_starts) {
+        writeln!(
+            w,
+            "  Multi-start: {c} of {s} starts converged to the best minimum"
+        )?;
+    }
+    writeln!(
+        w,
+        "  Solver: {iterations} iterations, {function_evaluations} function evaluations, {termination}"
+    )?;
+    if let (Some(l), Some(r)) = (loss, irls_rounds) {
+        writeln!(w, "  Robust loss: {l}, {r} reweighting rounds")?;
+    }
+    if let Some(b) = bootstrap {
+        let resample = b.resample.to_possible_value().expect("not skipped");
         writeln!(
             w,
-            "  Root Mean Squared Residual error: {}",
-            nfmtr.fmt2(*rmsr)
+            "  Bootstrap: {} resamples of {}, {} failed",
+            b.resamples,
+            resample.get_name(),
+            b.failed
         )?;
-        writeln!(w, "  R-sq Adjusted: {}", nfmtr.fmt2(*rsq))?;
     }
 
     Ok(())
@@ -419,3 +1309,33 @@ fn write_table(x: &Fit, write_stats: bool, table_fmt: &str, mut w: impl Write) -
 fn write_json_table(x: &Fit) -> Result<()> {
     serde_json::to_writer(io::stdout(), x).into_diagnostic()
 }
+
+/// Deserialise floats which may be `null`, as JSON has no representation of `NaN`.
+mod nullable {
+    use serde::{Deserialize, Deserializer};
+
+    fn nan(x: Option<f64>) -> f64 {
+        x.unwrap_or(f64::NAN)
+    }
+
+    /// Fields missing from older output are undefined.
+    pub fn missing() -> f64 {
+        f64::NAN
+    }
+
+    pub fn num<'de, D: Deserializer<'de>>(d: D) -> Result<f64, D::Error> {
+        Option::deserialize(d).map(nan)
+    }
+
+    pub fn vec<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<f64>, D::Error> {
+        Vec::<Option<f64>>::deserialize(d).map(|x| x.into_iter().map(nan).collect())
+    }
+
+    pub fn matrix<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Vec<f64>>, D::Error> {
+        Vec::<Vec<Option<f64>>>::deserialize(d).map(|x| {
+            x.into_iter()
+                .map(|x| x.into_iter().map(nan).collect())
+                .collect()
+        })
+    }
+}
@@ -0,0 +1,218 @@
+//! Bootstrap estimates of the parameter uncertainties.
+//!
+//! The data is resampled with replacement and refitted many times, the spread of the refitted
+//! parameters estimates their uncertainty without relying on the linearised covariance.
+use super::*;
+
+/// Bootstrap estimates of the parameter uncertainties, see [`FitOptions::bootstrap`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bootstrap {
+    /// What was resampled.
+    pub resample: Resample,
+
+    /// Number of resamples.
+    pub resamples: u64,
+
+    /// Number of resamples which failed to fit, and are excluded from the estimates.
+    pub failed: u64,
+
+    /// The bootstrap standard error of each parameter, the standard deviation of the refitted
+    /// values.
+    #[serde(deserialize_with = "nullable::vec")]
+    pub xerrs: Vec<f64>,
+
+    /// The lower limit of each parameters percentile confidence interval, at the fit's
+    /// confidence level.
+    #[serde(deserialize_with = "nullable::vec")]
+    pub ci_lower: Vec<f64>,
+
+    /// The upper limit of each parameters percentile confidence interval.
+    #[serde(deserialize_with = "nullable::vec")]
+    pub ci_upper: Vec<f64>,
+}
+
+/// Refit `opts.bootstrap` resamples of the data, starting from the parameters of `fit`.
+pub(super) fn run<E: Equation>(
+    eq: &E,
+    data: &Data,
+    target: &str,
+    opts: &FitOptions,
+    fit: &Fit,
+) -> Result<Bootstrap> {
+    let n = data.len();
+    let np = fit.parameter_values.len();
+    let tgt = data
+        .headers()
+        .find_ignore_case_and_ws(target)
+        .expect("target found when fitting");
+
+    // each resample refits from the fitted parameters, which is quicker and avoids jumping
+    // between local minima
+    let init = fit
+        .parameter_names
+        .iter()
+        .zip(&fit.parameter_values)
+        .filter(|(name, _)| !fit.fixed_parameters.contains(name))
+        .map(|(name, &value)| ParamValue {
+            name: name.clone(),
+            value,
+        })
+        .collect();
+    let inner = FitOptions {
+        init,
+        multistart: 0,
+        bootstrap: 0,
+        ..opts.clone()
+    };
+
+    // residual resampling keeps each observation's x, and adds a resampled residual to its
+    // prediction, scaled by the observation weights. Zero weight observations have no
+    // standardised residual to resample, and keep their prediction.
+    let resids = match opts.resample {
+        Resample::Rows => None,
+        Resample::Residuals => {
+            let y = data.num_col(tgt).expect("inside data")?;
+            let mut pred = vec![0.0; n];
+            eq.solve_batch(&fit.parameter_values, data, &mut pred)
+                .ok_or_else(|| miette!("failed to solve equation when resampling"))?;
+            let sqrt_wts = opts
+                .weights
+                .as_ref()
+                .map(|w| read_weights(data, w))
+                .transpose()?
+                .map(|w| w.into_iter().map(f64::sqrt).collect::<Vec<_>>());
+            let sw = |i: usize| sqrt_wts.as_ref().map_or(1.0, |w| w[i]);
+            let pool = (0..n).filter(|&i| sw(i) > 0.0).collect::<Vec<_>>();
+            ensure!(!pool.is_empty(), "every observation has a weight of zero");
+            let std_resid = pool
+                .iter()
+                .map(|&i| (y[i] - pred[i]) * sw(i))
+                .collect::<Vec<_>>();
+            Some((pred, std_resid, sqrt_wts))
+        }
+    };
+
+    let mut rng = opts.seed.map(fastrand::Rng::with_seed).unwrap_or_default();
+    let mut samples = vec![Vec::with_capacity(opts.bootstrap); np];
+    let mut failed = 0;
+    let mut idxs = vec![0; n];
+
+    for _ in 0..opts.bootstrap {
+        let sample = match &resids {
+            None => {
+                idxs.iter_mut().for_each(|i| *i = rng.usize(..n));
+                data.select(&idxs)
+            }
+            Some((pred, std_resid, sqrt_wts)) => {
+                let sw = |i: usize| sqrt_wts.as_ref().map_or(1.0, |w| w[i]);
+                let y = (0..n)
+                    .map(|i| match sw(i) {
+                        w if w > 0.0 => pred[i] + std_resid[rng.usize(..std_resid.len())] / w,
+                        _ => pred[i],
+                    })
+                    .collect();
+                let mut d = data.select(&(0..n).collect::<Vec<_>>());
+                d.set_num_col(tgt, y);
+                d
+            }
+        };
+
+        match fit_with(eq, &sample, target, &inner) {
+            Ok(f) => {
+                for (s, x) in samples.iter_mut().zip(f.parameter_values) {
+                    s.push(x);
+                }
+            }
+            Err(_) => failed += 1,
+        }
+    }
+
+    let ci_level = fit.ci_level;
+    let lo = (1.0 - ci_level) / 2.0;
+    let hi = 1.0 - lo;
+
+    let mut xerrs = Vec::with_capacity(np);
+    let mut ci_lower = Vec::with_capacity(np);
+    let mut ci_upper = Vec::with_capacity(np);
+    for mut s in samples {
+        xerrs.push(std_dev(&s));
+        s.sort_by(f64::total_cmp);
+        ci_lower.push(quantile(&s, lo));
+        ci_upper.push(quantile(&s, hi));
+    }
+
+    Ok(Bootstrap {
+        resample: opts.resample,
+        resamples: opts.bootstrap as u64,
+        failed,
+        xerrs,
+        ci_lower,
+        ci_upper,
+    })
+}
+
+/// Sample standard deviation.
+fn std_dev(xs: &[f64]) -> f64 {
+    let n = xs.len() as f64;
+    let mean = xs.iter().sum::<f64>() / n;
+    (xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+}
+
+/// The `p` quantile of sorted values, linearly interpolating between values.
+fn quantile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    let h = (sorted.len() - 1) as f64 * p;
+    let (i, f) = (h.floor() as usize, h.fract());
+    match sorted.get(i + 1) {
+        Some(b) => sorted[i] + f * (b - sorted[i]),
+        None => sorted[i],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantiles() {
+        let xs = [1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(quantile(&xs, 0.0), 1.0);
+        assert_eq!(quantile(&xs, 0.5), 3.0);
+        assert_eq!(quantile(&xs, 0.625), 3.5);
+        assert_eq!(quantile(&xs, 1.0), 5.0);
+        assert!(quantile(&[], 0.5).is_nan());
+
+        assert!((std_dev(&xs) - 2.5f64.sqrt()).abs() < 1e-15);
+    }
+
+    #[test]
+    fn residuals_skip_zero_weights() {
+        let data = Data::new(
+            Headers::from_iter(["x", "y", "w"]),
+            [
+                [0.0, 1.1, 1.0],
+                [1.0, 2.9, 1.0],
+                [2.0, 5.2, 0.0],
+                [3.0, 6.8, 2.0],
+                [4.0, 9.1, 1.0],
+                [5.0, 10.9, 1.0],
+            ],
+        )
+        .unwrap();
+        let eq = crate::expr::v1::Eq::parse("m * x + c", data.headers()).unwrap();
+        let opts = FitOptions {
+            weights: Some(Weights::Column("w".into())),
+            bootstrap: 50,
+            resample: Resample::Residuals,
+            seed: Some(1),
+            ..Default::default()
+        };
+
+        let b = fit_with(&eq, &data, "y", &opts).unwrap().bootstrap.unwrap();
+        assert_eq!(b.failed, 0);
+        assert!(b.xerrs.iter().all(|x| x.is_finite()));
+        assert!(b.ci_lower.iter().chain(&b.ci_upper).all(|x| x.is_finite()));
+    }
+}
@@ -0,0 +1,334 @@
+//! Comparing the fits of competing equations.
+use super::*;
+use std::collections::BTreeSet;
+
+/// The comparison of several equations fitted to the same data, see [`compare`].
+#[derive(Serialize)]
+pub struct Comparison {
+    /// The candidate fits, ranked by AIC (best first).
+    pub candidates: Vec<Candidate>,
+
+    /// F-tests between nested candidates, where the free parameters of the restricted candidate
+    /// are a subset of the full candidate's.
+    ///
+    /// Nesting is decided by the parameter names alone, the equations are not checked to be
+    /// special cases of each other (`m * x` and `m * ln(x) + c` would be tested), so the tests
+    /// are only valid for truly nested equations.
+    pub nested: Vec<NestedTest>,
+}
+
+/// An equation's fit, see [`Comparison`].
+#[derive(Serialize)]
+pub struct Candidate {
+    /// The equation.
+    pub expr: String,
+
+    /// The AIC difference to the best candidate.
+    pub delta_aic: f64,
+
+    /// The fit.
+    pub fit: Fit,
+}
+
+/// An F-test of whether the extra parameters of the full candidate significantly improve the fit
+/// over the restricted candidate.
+///
+/// The test assumes the restricted equation is the full equation with the extra parameters set
+/// to zero, which is not checked.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NestedTest {
+    /// The restricted equation.
+    pub restricted: String,
+
+    /// The full equation.
+    pub full: String,
+
+    /// The F-statistic.
+    pub fstat: f64,
+
+    /// The numerator degrees of freedom, the number of extra parameters.
+    pub df_num: f64,
+
+    /// The denominator degrees of freedom, the full candidate's residual degrees of freedom.
+    pub df_den: f64,
+
+    /// The p-value of the F-statistic.
+    pub pval: f64,
+}
+
+/// Fit each equation in `eqs` against the same `data` and rank the fits.
+///
+/// Parameter bounds, fixed values and initial values in `opts` apply to each equation containing
+/// the parameter. A parameter which is in none of the equations is an error.
+pub fn compare<E: Equation>(
+    eqs: &[E],
+    data: &Data,
+    target: &str,
+    opts: &FitOptions,
+) -> Result<Comparison> {
+    ensure!(!eqs.is_empty(), "no equations to compare");
+
+    let params = eqs.iter().flat_map(|e| e.params()).collect::<BTreeSet<_>>();
+    let names = opts
+        .bounds
+        .iter()
+        .map(|x| &x.name)
+        .chain(opts.fixed.iter().map(|x| &x.name))
+        .chain(opts.init.iter().map(|x| &x.name));
+    for name in names {
+        ensure!(
+            params.contains(name),
+            "parameter '{}' not found in any equation",
+            name
+        );
+    }
+
+    let fits = eqs
+        .iter()
+        .enumerate()
+        .map(|(i, eq)| {
+            let expr = eq.expr().unwrap_or_else(|| format!("equation {}", i + 1));
+            let opts = opts.restricted(&eq.params());
+            fit_with(eq, data, target, &opts)
+                .wrap_err_with(|| format!("fitting '{expr}'"))
+                .map(|fit| (expr, fit))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Comparison::new(fits))
+}
+
+impl Comparison {
+    /// Rank the fits of equations, and test any nested equations.
+    ///
+    /// The fits are expected to be of the same data.
+    pub fn new(fits: Vec<(String, Fit)>) -> Self {
+        let nested = nested_tests(&fits);
+
+        let mut candidates = fits
+            .into_iter()
+            .map(|(expr, fit)| Candidate {
+                expr,
+                delta_aic: 0.0,
+                fit,
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| {
+            a.fit
+                .aic
+                .total_cmp(&b.fit.aic)
+                .then(a.fit.bic.total_cmp(&b.fit.bic))
+        });
+
+        let best = candidates.first().map_or(f64::NAN, |c| c.fit.aic);
+        for c in &mut candidates {
+            c.delta_aic = c.fit.aic - best;
+        }
+
+        Self { candidates, nested }
+    }
+
+    /// Write the comparison in a particular output format to a writer.
+    pub fn write_results<W: Write>(&self, output: Output, wtr: W) -> Result<()> {
+        use comfy_table::presets::*;
+        match output {
+            Output::Table => self.write_table(UTF8_HORIZONTAL_ONLY, wtr),
+            Output::Plain => self.write_table(NOTHING, wtr),
+            Output::Csv => self.write_csv(wtr),
+            Output::Md => self.write_table(ASCII_MARKDOWN, wtr),
+            Output::Json => return serde_json::to_writer(wtr, self).into_diagnostic(),
+        }
+        .into_diagnostic()
+    }
+
+    fn write_table(&self, table_fmt: &str, mut w: impl Write) -> io::Result<()> {
+        use comfy_table::{Cell, CellAlignment as CA, Table};
+
+        let mut nfmtr = nfmtr();
+        let mut num = |x: f64| Cell::new(nfmtr.fmt2(x)).set_alignment(CA::Right);
+
+        let mut table = Table::new();
+        table.set_header(RANKING_HEADERS);
+        for (i, c) in self.candidates.iter().enumerate() {
+            table.add_row([
+                Cell::new(i + 1),
+                Cell::new(&c.expr),
+                Cell::new(free_params(&c.fit).len()).set_alignment(CA::Right),
+                num(c.fit.aic),
+                num(c.delta_aic),
+                num(c.fit.bic),
+                num(c.fit.rsq),
+                num(c.fit.rmsr),
+            ]);
+        }
+        table.load_preset(table_fmt);
+        writeln!(w, "{table}")?;
+
+        if !self.nested.is_empty() {
+            let mut table = Table::new();
+            table.set_header(NESTED_HEADERS);
+            for t in &self.nested {
+                table.add_row([
+                    Cell::new(&t.restricted),
+                    Cell::new(&t.full),
+                    num(t.fstat),
+                    num(t.df_num),
+                    num(t.df_den),
+                    num(t.pval),
+                ]);
+            }
+            table.load_preset(table_fmt);
+            writeln!(w)?;
+            writeln!(w, "{table}")?;
+        }
+
+        Ok(())
+    }
+
+    fn write_csv(&self, mut wtr: impl Write) -> io::Result<()> {
+        let mut w = csv::Writer::from_writer(&mut wtr);
+        w.write_record(RANKING_HEADERS)?;
+        for (i, c) in self.candidates.iter().enumerate() {
+            w.write_record([
+                (i + 1).to_string(),
+                c.expr.clone(),
+                free_params(&c.fit).len().to_string(),
+                c.fit.aic.to_string(),
+                c.delta_aic.to_string(),
+                c.fit.bic.to_string(),
+                c.fit.rsq.to_string(),
+                c.fit.rmsr.to_string(),
+            ])?;
+        }
+        drop(w);
+
+        if !self.nested.is_empty() {
+            writeln!(wtr)?;
+            let mut w = csv::Writer::from_writer(&mut wtr);
+            w.write_record(NESTED_HEADERS)?;
+            for t in &self.nested {
+                w.write_record([
+                    t.restricted.clone(),
+                    t.full.clone(),
+                    t.fstat.to_string(),
+                    t.df_num.to_string(),
+                    t.df_den.to_string(),
+                    t.pval.to_string(),
+                ])?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+const RANKING_HEADERS: [&str; 8] = [
+    "Rank",
+    "Expression",
+    "Parameters",
+    "AIC",
+    "ΔAIC",
+    "BIC",
+    "R-sq Adjusted",
+    "RMSR",
+];
+
+const NESTED_HEADERS: [&str; 6] = [
+    "Restricted",
+    "Full",
+    "F-statistic",
+    "df num",
+    "df den",
+    "p-value",
+];
+
+/// The names of the parameters which were fitted, not held fixed.
+fn free_params(fit: &Fit) -> BTreeSet<&str> {
+    fit.parameter_names
+        .iter()
+        .filter(|p| !fit.fixed_parameters.contains(p))
+        .map(String::as_str)
+        .collect()
+}
+
+/// F-tests of each pair of fits where one's free parameters are a strict subset of the other's.
+///
+/// Only the parameter names are compared, not the equations.
+fn nested_tests(fits: &[(String, Fit)]) -> Vec<NestedTest> {
+    let mut tests = Vec::new();
+
+    for (restricted, r) in fits {
+        let rp = free_params(r);
+        for (full, f) in fits {
+            let fp = free_params(f);
+            if rp.len() >= fp.len() || !rp.is_subset(&fp) {
+                continue;
+            }
+
+            let df_num = r.dfr - f.dfr;
+            let df_den = f.dfr;
+            let fstat = ((r.ssr - f.ssr) / df_num) / (f.ssr / df_den);
+            tests.push(NestedTest {
+                restricted: restricted.clone(),
+                full: full.clone(),
+                fstat,
+                df_num,
+                df_den,
+                pval: stats::f_pvalue(fstat, df_num, df_den),
+            });
+        }
+    }
+
+    tests
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::v1::Eq;
+
+    #[test]
+    fn ranks_and_nests() {
+        // quadratic data
+        let data = Data::new(
+            Headers::from_iter(["x", "y"]),
+            (0..12).map(|i| {
+                let x = i as f64 * 0.5;
+                let noise = if i % 2 == 0 { 0.05 } else { -0.05 };
+                [x, 0.8 * x * x - 1.2 * x + 2.0 + noise]
+            }),
+        )
+        .unwrap();
+        let eqs = ["m * x + c", "a * x^2 + m * x + c", "c"]
+            .map(|e| Eq::parse(e, data.headers()).unwrap());
+
+        let cmp = compare(&eqs, &data, "y", &FitOptions::default()).unwrap();
+
+        let ranked = cmp
+            .candidates
+            .iter()
+            .map(|c| c.expr.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ranked, ["a * x^2 + m * x + c", "m * x + c", "c"]);
+        assert_eq!(cmp.candidates[0].delta_aic, 0.0);
+        assert!(cmp.candidates[1].delta_aic > 0.0);
+
+        // c ⊂ m * x + c ⊂ a * x^2 + m * x + c
+        assert_eq!(cmp.nested.len(), 3);
+        let t = cmp
+            .nested
+            .iter()
+            .find(|t| t.restricted == "m * x + c")
+            .unwrap();
+        assert_eq!(t.full, "a * x^2 + m * x + c");
+        assert_eq!(t.df_num, 1.0);
+        assert!(t.pval < 1e-6);
+
+        // a fixed parameter must be in at least one equation
+        let opts = FitOptions {
+            fixed: vec!["k=1".parse().unwrap()],
+            ..Default::default()
+        };
+        assert!(compare(&eqs, &data, "y", &opts).is_err());
+    }
+}
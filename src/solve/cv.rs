@@ -0,0 +1,289 @@
+//! K-fold cross-validation of a fit.
+use super::*;
+
+/// The out-of-sample performance of a fit, see [`cross_validate`].
+#[derive(Serialize)]
+pub struct CrossValidation {
+    /// The names of the parameters.
+    pub parameter_names: Vec<String>,
+
+    /// Each fold, fitted on the other folds and scored on its held-out observations.
+    pub folds: Vec<Fold>,
+
+    /// Mean of the folds' out-of-sample root mean squared error.
+    pub rmse_mean: f64,
+
+    /// Standard deviation of the folds' out-of-sample root mean squared error.
+    pub rmse_sd: f64,
+
+    /// Mean of the folds' out-of-sample R squared.
+    pub rsq_mean: f64,
+
+    /// Standard deviation of the folds' out-of-sample R squared.
+    pub rsq_sd: f64,
+}
+
+/// A single cross-validation fold.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Fold {
+    /// Number of held-out observations.
+    pub n: u64,
+
+    /// The parameter values fitted on the other folds.
+    pub parameter_values: Vec<f64>,
+
+    /// Root mean squared error of the held-out observations.
+    pub rmse: f64,
+
+    /// R squared of the held-out observations, relative to their mean.
+    pub rsq: f64,
+}
+
+/// Cross-validate the fit of `eq` by splitting `data` into `k` folds.
+///
+/// The rows are shuffled into folds with the random number generator seeded with
+/// [`FitOptions::seed`]. Each fold is held out in turn, the equation is fitted on the remaining
+/// folds with `opts`, and scored on the held-out observations.
+/// The scores are unweighted. Every fold holds out at least two observations, so that its R
+/// squared is defined, which limits `k` to half the number of observations.
+pub fn cross_validate<E: Equation>(
+    eq: &E,
+    data: &Data,
+    target: &str,
+    opts: &FitOptions,
+    k: usize,
+) -> Result<CrossValidation> {
+    let n = data.len();
+    ensure!(
+        k >= 2 && k <= n / 2,
+        "number of folds must be between 2 and half the number of observations ({}), found {}",
+        n,
+        k
+    );
+
+    let tgt = data
+        .headers()
+        .find_ignore_case_and_ws(target)
+        .ok_or_else(|| miette!("could not find column '{}' in headers", target))
+        .wrap_err_with(|| data::match_hdr_help(data.headers(), target))?;
+    let y = data
+        .num_col(tgt)
+        .ok_or_else(|| miette!("column index {} not in table", tgt))??;
+
+    let mut rng = opts.seed.map(fastrand::Rng::with_seed).unwrap_or_default();
+    let mut rows = (0..n).collect::<Vec<_>>();
+    rng.shuffle(&mut rows);
+
+    let opts = FitOptions {
+        bootstrap: 0,
+        ..opts.clone()
+    };
+
+    let folds = (0..k)
+        .map(|f| {
+            let (test, train): (Vec<_>, Vec<_>) =
+                rows.iter().enumerate().partition(|(i, _)| i % k == f);
+            let test = test.into_iter().map(|(_, &r)| r).collect::<Vec<_>>();
+            let train = train.into_iter().map(|(_, &r)| r).collect::<Vec<_>>();
+
+            let fit = fit_with(eq, &data.select(&train), target, &opts)
+                .wrap_err_with(|| format!("fitting fold {}", f + 1))?;
+
+            let held = data.select(&test);
+            let mut pred = vec![0.0; held.len()];
+            eq.solve_batch(&fit.parameter_values, &held, &mut pred)
+                .ok_or_else(|| miette!("failed to solve equation when scoring"))
+                .wrap_err_with(|| format!("scoring fold {}", f + 1))?;
+
+            let y = test.iter().map(|&r| y[r]).collect::<Vec<_>>();
+            let m = y.len() as f64;
+            let mean = y.iter().sum::<f64>() / m;
+            let ssr = y
+                .iter()
+                .zip(&pred)
+                .map(|(y, p)| (y - p).powi(2))
+                .sum::<f64>();
+            let sst = y.iter().map(|y| (y - mean).powi(2)).sum::<f64>();
+
+            Ok(Fold {
+                n: test.len() as u64,
+                parameter_values: fit.parameter_values,
+                rmse: (ssr / m).sqrt(),
+                rsq: 1.0 - ssr / sst,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let (rmse_mean, rmse_sd) = mean_sd(folds.iter().map(|f| f.rmse));
+    let (rsq_mean, rsq_sd) = mean_sd(folds.iter().map(|f| f.rsq));
+
+    Ok(CrossValidation {
+        parameter_names: eq.params(),
+        folds,
+        rmse_mean,
+        rmse_sd,
+        rsq_mean,
+        rsq_sd,
+    })
+}
+
+/// Mean and sample standard deviation.
+fn mean_sd(xs: impl ExactSizeIterator<Item = f64> + Clone) -> (f64, f64) {
+    let n = xs.len() as f64;
+    let mean = xs.clone().sum::<f64>() / n;
+    let var = xs.map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, var.sqrt())
+}
+
+impl CrossValidation {
+    /// Write the cross-validation in a particular output format to a writer.
+    ///
+    /// `write_statistics` writes the summary of the folds' errors.
+    pub fn write_results<W: Write>(
+        &self,
+        output: Output,
+        write_statistics: bool,
+        wtr: W,
+    ) -> Result<()> {
+        use comfy_table::presets::*;
+        let s = write_statistics;
+        match output {
+            Output::Table => self.write_table(s, UTF8_HORIZONTAL_ONLY, wtr),
+            Output::Plain => self.write_table(s, NOTHING, wtr),
+            Output::Csv => self.write_csv(s, wtr),
+            Output::Md => self.write_table(s, ASCII_MARKDOWN, wtr),
+            Output::Json => return serde_json::to_writer(wtr, self).into_diagnostic(),
+        }
+        .into_diagnostic()
+    }
+
+    fn headers(&self) -> Vec<&str> {
+        ["Fold", "Observations", "RMSE", "R-sq"]
+            .into_iter()
+            .chain(self.parameter_names.iter().map(String::as_str))
+            .collect()
+    }
+
+    fn write_table(&self, write_stats: bool, table_fmt: &str, mut w: impl Write) -> io::Result<()> {
+        use comfy_table::{Cell, CellAlignment as CA, Row, Table};
+
+        let mut nfmtr = nfmtr();
+
+        let mut table = Table::new();
+        table.set_header(self.headers());
+        for (i, f) in self.folds.iter().enumerate() {
+            let mut row = Row::new();
+            row.add_cell(Cell::new(i + 1))
+                .add_cell(Cell::new(f.n).set_alignment(CA::Right));
+            for &x in [f.rmse, f.rsq].iter().chain(&f.parameter_values) {
+                row.add_cell(Cell::new(nfmtr.fmt2(x)).set_alignment(CA::Right));
+            }
+            table.add_row(row);
+        }
+        table.load_preset(table_fmt);
+        writeln!(w, "{table}")?;
+
+        if write_stats {
+            self.write_summary(&mut nfmtr, w)?;
+        }
+        Ok(())
+    }
+
+    fn write_csv(&self, write_stats: bool, mut wtr: impl Write) -> io::Result<()> {
+        let mut w = csv::Writer::from_writer(&mut wtr);
+        w.write_record(self.headers())?;
+        for (i, f) in self.folds.iter().enumerate() {
+            w.write_field((i + 1).to_string())?;
+            w.write_field(f.n.to_string())?;
+            for x in [f.rmse, f.rsq].iter().chain(&f.parameter_values) {
+                w.write_field(x.to_string())?;
+            }
+            w.write_record(None::<&[u8]>)?;
+        }
+        drop(w);
+
+        if write_stats {
+            self.write_summary(&mut nfmtr(), wtr)?;
+        }
+        Ok(())
+    }
+
+    fn write_summary(&self, nfmtr: &mut numfmt::Formatter, mut w: impl Write) -> io::Result<()> {
+        writeln!(w, "  Folds: {}", self.folds.len())?;
+        writeln!(w, "  Out-of-sample RMSE: {}", nfmtr.fmt2(self.rmse_mean))?;
+        writeln!(
+            w,
+            "  Out-of-sample RMSE std dev: {}",
+            nfmtr.fmt2(self.rmse_sd)
+        )?;
+        writeln!(w, "  Out-of-sample R-sq: {}", nfmtr.fmt2(self.rsq_mean))?;
+        writeln!(
+            w,
+            "  Out-of-sample R-sq std dev: {}",
+            nfmtr.fmt2(self.rsq_sd)
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::v1::Eq;
+
+    #[test]
+    fn folds_hold_out_every_row() {
+        let data = Data::new(
+            Headers::from_iter(["x", "y"]),
+            (0..23).map(|i| {
+                let x = i as f64;
+                let noise = [0.1, -0.2, 0.05][i % 3];
+                [x, 2.0 * x + 1.0 + noise]
+            }),
+        )
+        .unwrap();
+        let eq = Eq::parse("m * x + c", data.headers()).unwrap();
+        let opts = FitOptions {
+            seed: Some(3),
+            ..Default::default()
+        };
+
+        let cv = cross_validate(&eq, &data, "y", &opts, 5).unwrap();
+
+        assert_eq!(cv.folds.len(), 5);
+        assert_eq!(cv.folds.iter().map(|f| f.n).sum::<u64>(), 23);
+        assert!(cv.folds.iter().all(|f| f.n == 4 || f.n == 5));
+        assert!(cv.rmse_mean < 0.3);
+        assert!(cv.rsq_mean > 0.99);
+        for f in &cv.folds {
+            assert!((f.parameter_values[1] - 2.0).abs() < 0.05);
+        }
+
+        assert!(cross_validate(&eq, &data, "y", &opts, 1).is_err());
+        assert!(cross_validate(&eq, &data, "y", &opts, 11).is_ok());
+        assert!(cross_validate(&eq, &data, "y", &opts, 12).is_err());
+    }
+
+    #[test]
+    fn folds_observe_every_level() {
+        let mut data = Data::new(
+            Headers::from_iter(["g", "y"]),
+            ["a", "a", "a", "a", "a", "b"]
+                .iter()
+                .enumerate()
+                .map(|(i, g)| [Cell::from(*g), Cell::from(i as f64)]),
+        )
+        .unwrap();
+        data.set_categorical(0).unwrap();
+        let eq = Eq::parse("a * g + c", data.headers()).unwrap();
+
+        // holding out the only `b` leaves a fold without the level
+        let Err(e) = cross_validate(&eq, &data, "y", &FitOptions::default(), 3) else {
+            panic!("expecting a fold without the level");
+        };
+        assert_eq!(
+            e.root_cause().to_string(),
+            "categorical column 'g' has no observations of level 'b'"
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! Fitting groups of rows independently.
+use super::*;
+
+/// Independent fits of each group of rows, see [`fit_groups`].
+#[derive(Serialize)]
+pub struct Grouped {
+    /// The column the rows are grouped by.
+    pub column: String,
+
+    /// The fit of each group, in the order the group first appears in the data.
+    pub groups: Vec<Group>,
+}
+
+/// The fit of a group of rows, see [`Grouped`].
+#[derive(Serialize)]
+pub struct Group {
+    /// The value of the grouping column.
+    pub group: String,
+
+    /// The fit.
+    pub fit: Fit,
+}
+
+/// Partition the rows of `data` by the values of the `column`, and fit `eq` to each group.
+///
+/// The column is typically text, such as a category, but numbers group by their value.
+pub fn fit_groups<E: Equation>(
+    eq: &E,
+    data: &Data,
+    target: &str,
+    opts: &FitOptions,
+    column: &str,
+) -> Result<Grouped> {
+    let colidx = data
+        .headers()
+        .find_ignore_case_and_ws(column)
+        .ok_or_else(|| miette!("could not find column '{}' in headers", column))
+        .wrap_err_with(|| data::match_hdr_help(data.headers(), column))?;
+
+    let groups = data
+        .groups(colidx)
+        .expect("column found in headers")
+        .into_iter()
+        .map(|(group, rows)| {
+            fit_with(eq, &data.select(&rows), target, opts)
+                .wrap_err_with(|| format!("fitting group '{group}'"))
+                .map(|fit| Group { group, fit })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Grouped {
+        column: data
+            .headers()
+            .iter()
+            .nth(colidx)
+            .expect("inside headers")
+            .to_string(),
+        groups,
+    })
+}
+
+impl Grouped {
+    /// Write the fits in a particular output format to a writer, as one table of parameters keyed
+    /// by group.
+    ///
+    /// `write_statistics` writes a table of each group's goodness-of-fit statistics.
+    pub fn write_results<W: Write>(
+        &self,
+        output: Output,
+        write_statistics: bool,
+        wtr: W,
+    ) -> Result<()> {
+        use comfy_table::presets::*;
+        let s = write_statistics;
+        match output {
+            Output::Table => self.write_table(s, UTF8_HORIZONTAL_ONLY, wtr),
+            Output::Plain => self.write_table(s, NOTHING, wtr),
+            Output::Csv => self.write_csv(s, wtr),
+            Output::Md => self.write_table(s, ASCII_MARKDOWN, wtr),
+            Output::Json => return serde_json::to_writer(wtr, self).into_diagnostic(),
+        }
+        .into_diagnostic()
+    }
+
+    fn param_headers(&self) -> Vec<String> {
+        // groups share the confidence level
+        let level = self.groups.first().map_or(0.95, |g| g.fit.ci_level);
+        let [lo, hi] = ci_headers(level);
+        [
+            self.column.as_str(),
+            "Parameter",
+            "Value",
+            "Standard Error",
+            "t-value",
+            "p-value",
+            &lo,
+            &hi,
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    fn stats_headers(&self) -> [&str; 7] {
+        [
+            &self.column,
+            "Observations",
+            "RMSR",
+            "R-sq",
+            "R-sq Adjusted",
+            "AIC",
+            "BIC",
+        ]
+    }
+
+    /// Each parameter's row, with fixed parameter statistics blanked.
+    fn param_rows(&self) -> impl Iterator<Item = (&str, &str, bool, [Option<f64>; 6])> {
+        self.groups.iter().flat_map(|g| {
+            let x = &g.fit;
+            x.parameter_names.iter().enumerate().map(move |(i, p)| {
+                let fixed = x.fixed_parameters.contains(p);
+                let stat = |x: &[f64]| Some(x[i]).filter(|_| !fixed);
+                let row = [
+                    Some(x.parameter_values[i]),
+                    stat(&x.xerrs),
+                    stat(&x.tvals),
+                    stat(&x.pvals),
+                    stat(&x.ci_lower),
+                    stat(&x.ci_upper),
+                ];
+                (g.group.as_str(), p.as_str(), fixed, row)
+            })
+        })
+    }
+
+    fn write_table(&self, write_stats: bool, table_fmt: &str, mut w: impl Write) -> io::Result<()> {
+        use comfy_table::{Cell, CellAlignment as CA, Row, Table};
+
+        let mut nfmtr = nfmtr();
+        let mut cell = |x: Option<f64>| {
+            let s = x.map(|x| nfmtr.fmt2(x).to_string()).unwrap_or_default();
+            Cell::new(s).set_alignment(CA::Right)
+        };
+
+        let mut table = Table::new();
+        table.set_header(self.param_headers());
+        for (group, p, fixed, xs) in self.param_rows() {
+            let mut row = Row::new();
+            row.add_cell(Cell::new(group)).add_cell(Cell::new(p));
+            for (j, x) in xs.into_iter().enumerate() {
+                if fixed && j == 1 {
+                    row.add_cell(Cell::new("fixed").set_alignment(CA::Right));
+                } else {
+                    row.add_cell(cell(x));
+                }
+            }
+            table.add_row(row);
+        }
+        table.load_preset(table_fmt);
+        writeln!(w, "{table}")?;
+
+        if write_stats {
+            let mut table = Table::new();
+            table.set_header(self.stats_headers());
+            for g in &self.groups {
+                let x = &g.fit;
+                let mut row = Row::new();
+                row.add_cell(Cell::new(&g.group))
+                    .add_cell(Cell::new(x.n).set_alignment(CA::Right));
+                for s in [x.rmsr, x.rsq_unadjusted, x.rsq, x.aic, x.bic] {
+                    row.add_cell(cell(Some(s)));
+                }
+                table.add_row(row);
+            }
+            table.load_preset(table_fmt);
+            writeln!(w)?;
+            writeln!(w, "{table}")?;
+        }
+
+        Ok(())
+    }
+
+    fn write_csv(&self, write_stats: bool, mut wtr: impl Write) -> io::Result<()> {
+        let mut w = csv::Writer::from_writer(&mut wtr);
+        w.write_record(self.param_headers())?;
+        for (group, p, fixed, xs) in self.param_rows() {
+            w.write_field(group)?;
+            w.write_field(p)?;
+            for (j, x) in xs.into_iter().enumerate() {
+                if fixed && j == 1 {
+                    w.write_field("fixed")?;
+                } else {
+                    w.write_field(x.map(|x| x.to_string()).unwrap_or_default())?;
+                }
+            }
+            w.write_record(None::<&[u8]>)?;
+        }
+        drop(w);
+
+        if write_stats {
+            writeln!(wtr)?;
+            let mut w = csv::Writer::from_writer(&mut wtr);
+            w.write_record(self.stats_headers())?;
+            for g in &self.groups {
+                let x = &g.fit;
+                w.write_field(&g.group)?;
+                w.write_field(x.n.to_string())?;
+                for s in [x.rmsr, x.rsq_unadjusted, x.rsq, x.aic, x.bic] {
+                    w.write_field(s.to_string())?;
+                }
+                w.write_record(None::<&[u8]>)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::v1::Eq;
+
+    #[test]
+    fn fits_each_group() {
+        let data = Data::new(
+            Headers::from_iter(["g", "x", "y"]),
+            (0..12).map(|i| {
+                let x = (i / 2) as f64;
+                let (g, y) = if i % 2 == 0 {
+                    ("a", 2.0 * x + 1.0)
+                } else {
+                    ("b", -0.5 * x + 4.0)
+                };
+                [
+                    Cell::from(g),
+                    Cell::from(x),
+                    Cell::from(y + 0.01 * (i % 3) as f64),
+                ]
+            }),
+        )
+        .unwrap();
+        let eq = Eq::parse("m * x + c", data.headers()).unwrap();
+
+        let grouped = fit_groups(&eq, &data, "y", &FitOptions::default(), "G").unwrap();
+
+        assert_eq!(grouped.column, "g");
+        let gs = grouped
+            .groups
+            .iter()
+            .map(|g| (g.group.as_str(), g.fit.n, g.fit.parameter_values[1]))
+            .collect::<Vec<_>>();
+        assert_eq!(gs.len(), 2);
+        assert_eq!((gs[0].0, gs[0].1), ("a", 6));
+        assert!((gs[0].2 - 2.0).abs() < 0.01);
+        assert_eq!((gs[1].0, gs[1].1), ("b", 6));
+        assert!((gs[1].2 + 0.5).abs() < 0.01);
+
+        assert!(fit_groups(&eq, &data, "y", &FitOptions::default(), "h").is_err());
+    }
+}
@@ -0,0 +1,378 @@
+//! A Levenberg-Marquardt solver which uses a supplied Jacobian.
+//!
+//! `rmpfit` only supports finite difference derivatives, this solver mirrors its interface (taking
+//! a [`MPConfig`] and returning a [`MPStatus`]) so the two can be used interchangeably.
+use rmpfit::{MPConfig, MPError, MPFitter, MPPar, MPResult, MPStatus, MPSuccess};
+
+/// A [`MPFitter`] which can also supply the Jacobian of its deviates.
+pub trait Jacobian: MPFitter {
+    /// Fill `jac` with the partial derivatives of each deviate with respect to each parameter.
+    ///
+    /// `jac` is row-major, with `number_of_points()` rows of `params.len()` derivatives.
+    fn jacobian(&self, params: &[f64], jac: &mut [f64]) -> MPResult<()>;
+}
+
+/// Refine the parameters `x` using the Levenberg-Marquardt technique.
+///
+/// Fixed parameters are held constant, bounded parameters are projected back into their limits
+/// after each step.
+pub fn lmfit<F: Jacobian>(
+    f: &F,
+    x: &mut [f64],
+    pars: Option<&[MPPar]>,
+    config: &MPConfig,
+) -> MPResult<MPStatus> {
+    let m = f.number_of_points();
+    let npar = x.len();
+
+    let dflt = MPPar::default();
+    let par = |j: usize| pars.and_then(|p| p.get(j)).unwrap_or(&dflt);
+    let lo = (0..npar)
+        .map(|j| {
+            Some(par(j))
+                .filter(|p| p.limited_low)
+                .map_or(f64::NEG_INFINITY, |p| p.limit_low)
+        })
+        .collect::<Vec<_>>();
+    let hi = (0..npar)
+        .map(|j| {
+            Some(par(j))
+                .filter(|p| p.limited_up)
+                .map_or(f64::INFINITY, |p| p.limit_up)
+        })
+        .collect::<Vec<_>>();
+    let ifree = (0..npar).filter(|&j| !par(j).fixed).collect::<Vec<_>>();
+
+    if m == 0 {
+        return Err(MPError::Empty);
+    }
+    if ifree.is_empty() {
+        return Err(MPError::NoFree);
+    }
+    if m < ifree.len() {
+        return Err(MPError::DoF);
+    }
+    if (0..npar).any(|j| lo[j] > hi[j]) {
+        return Err(MPError::Bounds);
+    }
+    if (0..npar).any(|j| x[j] < lo[j] || x[j] > hi[j]) {
+        return Err(MPError::InitBounds);
+    }
+
+    let mut r = vec![0.0; m];
+    let mut rnew = vec![0.0; m];
+    let mut xnew = vec![0.0; npar];
+    let mut jac = vec![0.0; m * npar];
+    let mut a = vec![0.0; npar * npar];
+    let mut g = vec![0.0; npar];
+
+    f.eval(x, &mut r)?;
+    let mut nfev = 1;
+    let mut chi2 = norm2(&r);
+    if !chi2.is_finite() {
+        return Err(MPError::Nan);
+    }
+    let orig_norm = chi2;
+
+    // the damping starts at zero (a Gauss-Newton step), which solves linear problems in a single
+    // step, and is increased when a step fails to reduce the residuals
+    let mut lambda = 0.0;
+    let mut iter = 0;
+    let mut success = MPSuccess::NotDone;
+    let mut active = ifree.clone();
+
+    while success == MPSuccess::NotDone {
+        if iter >= config.max_iter || (config.max_fev > 0 && nfev >= config.max_fev) {
+            success = MPSuccess::MaxIter;
+            break;
+        }
+        iter += 1;
+
+        f.jacobian(x, &mut jac)?;
+        normal_equations(&jac, &r, npar, &mut a, &mut g);
+
+        // parameters pegged at a limit, which the descent direction (-g) pushes beyond, are not
+        // varied this iteration
+        active = ifree
+            .iter()
+            .copied()
+            .filter(|&j| !((x[j] <= lo[j] && g[j] > 0.0) || (x[j] >= hi[j] && g[j] < 0.0)))
+            .collect();
+        if active.is_empty() {
+            success = MPSuccess::Dir;
+            break;
+        }
+
+        let n = active.len();
+        let a = submatrix(&a, npar, &active);
+        let g = active.iter().map(|&j| g[j]).collect::<Vec<_>>();
+
+        // the cosine of the angle between the residuals and the jacobian columns
+        let gnorm = (0..n)
+            .map(|j| {
+                let d = a[j * n + j].sqrt();
+                if d > 0.0 && chi2 > 0.0 {
+                    g[j].abs() / (d * chi2.sqrt())
+                } else {
+                    0.0
+                }
+            })
+            .fold(0.0, f64::max);
+        if gnorm <= config.gtol {
+            success = MPSuccess::Dir;
+            break;
+        }
+
+        let floor = f64::EPSILON * (0..n).map(|j| a[j * n + j]).fold(1.0, f64::max);
+
+        loop {
+            let mut damped = a.clone();
+            for j in 0..n {
+                damped[j * n + j] += lambda * a[j * n + j].max(floor);
+            }
+
+            let step = cholesky_solve(&mut damped, n, &g).map(|s| {
+                // project the step into the bounds, the step becomes the _actual_ step taken
+                xnew.copy_from_slice(x);
+                active
+                    .iter()
+                    .zip(s)
+                    .map(|(&j, s)| {
+                        xnew[j] = (x[j] - s).clamp(lo[j], hi[j]);
+                        x[j] - xnew[j]
+                    })
+                    .collect::<Vec<_>>()
+            });
+
+            let chi2new = match &step {
+                Some(_) => {
+                    f.eval(&xnew, &mut rnew)?;
+                    nfev += 1;
+                    norm2(&rnew)
+                }
+                None => f64::INFINITY,
+            };
+
+            if let Some(step) = step.filter(|_| chi2new.is_finite() && chi2new <= chi2) {
+                let actred = chi2 - chi2new;
+                // predicted reduction of the linearised model
+                let prered = 2.0 * dot(&step, &g) - quad(&a, &step);
+                let rho = if prered > 0.0 { actred / prered } else { 0.0 };
+
+                let ftol = chi2 == 0.0 || actred / chi2 <= config.ftol;
+                let xtol = norm2(&step).sqrt() <= config.xtol * norm2(x).sqrt();
+
+                x.copy_from_slice(&xnew);
+                std::mem::swap(&mut r, &mut rnew);
+                chi2 = chi2new;
+
+                if rho > 0.75 {
+                    lambda /= 10.0;
+                    if lambda < 1e-7 {
+                        lambda = 0.0;
+                    }
+                } else if rho < 0.25 {
+                    lambda = (lambda * 2.0).max(1e-3);
+                }
+
+                success = match (ftol, xtol) {
+                    (true, true) => MPSuccess::Both,
+                    (true, false) => MPSuccess::Chi,
+                    (false, true) => MPSuccess::Par,
+                    (false, false) => MPSuccess::NotDone,
+                };
+                break;
+            }
+
+            lambda = (lambda * 10.0).max(1e-3);
+            if lambda > 1e16 {
+                success = MPSuccess::Ftol;
+                break;
+            }
+            if config.max_fev > 0 && nfev >= config.max_fev {
+                success = MPSuccess::MaxIter;
+                break;
+            }
+        }
+    }
+
+    // covariance at the solution, fixed and pegged parameters have zero covariance
+    f.jacobian(x, &mut jac)?;
+    normal_equations(&jac, &r, npar, &mut a, &mut g);
+    let n = active.len();
+    let inv = invert(submatrix(&a, npar, &active), n, config.covtol);
+    let mut covar = vec![0.0; npar * npar];
+    for (i, &ai) in active.iter().enumerate() {
+        for (j, &aj) in active.iter().enumerate() {
+            covar[ai * npar + aj] = inv[i * n + j];
+        }
+    }
+    let xerror = (0..npar)
+        .map(|j| covar[j * npar + j].max(0.0).sqrt())
+        .collect();
+
+    Ok(MPStatus {
+        success,
+        best_norm: chi2,
+        orig_norm,
+        n_iter: iter,
+        n_fev: nfev,
+        n_par: npar,
+        n_free: ifree.len(),
+        n_pegged: ifree.len() - active.len(),
+        n_func: m,
+        resid: r,
+        xerror,
+        covar,
+    })
+}
+
+/// Extract the rows and columns `idx` of the `n` by `n` matrix `a`.
+fn submatrix(a: &[f64], n: usize, idx: &[usize]) -> Vec<f64> {
+    idx.iter()
+        .flat_map(|&i| idx.iter().map(move |&j| a[i * n + j]))
+        .collect()
+}
+
+fn norm2(xs: &[f64]) -> f64 {
+    xs.iter().map(|x| x * x).sum()
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// The quadratic form `xᵀ a x`.
+fn quad(a: &[f64], x: &[f64]) -> f64 {
+    a.chunks_exact(x.len())
+        .zip(x)
+        .map(|(row, xi)| xi * dot(row, x))
+        .sum()
+}
+
+/// Build `a = JᵀJ` and `g = Jᵀr`.
+fn normal_equations(jac: &[f64], r: &[f64], n: usize, a: &mut [f64], g: &mut [f64]) {
+    a.fill(0.0);
+    g.fill(0.0);
+
+    for (row, r) in jac.chunks_exact(n).zip(r) {
+        for i in 0..n {
+            g[i] += row[i] * r;
+            for j in 0..=i {
+                a[i * n + j] += row[i] * row[j];
+            }
+        }
+    }
+
+    // symmetrise
+    for i in 0..n {
+        for j in 0..i {
+            a[j * n + i] = a[i * n + j];
+        }
+    }
+}
+
+/// Solve `a x = b` where `a` is symmetric positive definite.
+///
+/// `a` is overwritten with its Cholesky factor.
+/// Returns `None` if `a` is not positive definite.
+fn cholesky_solve(a: &mut [f64], n: usize, b: &[f64]) -> Option<Vec<f64>> {
+    for j in 0..n {
+        let d = a[j * n + j] - (0..j).map(|k| a[j * n + k].powi(2)).sum::<f64>();
+        if d <= 0.0 || !d.is_finite() {
+            return None;
+        }
+        let d = d.sqrt();
+        a[j * n + j] = d;
+        for i in j + 1..n {
+            let s = (0..j).map(|k| a[i * n + k] * a[j * n + k]).sum::<f64>();
+            a[i * n + j] = (a[i * n + j] - s) / d;
+        }
+    }
+
+    // forward substitution, L y = b
+    let mut x = b.to_vec();
+    for i in 0..n {
+        let s = (0..i).map(|k| a[i * n + k] * x[k]).sum::<f64>();
+        x[i] = (x[i] - s) / a[i * n + i];
+    }
+    // back substitution, Lᵀ x = y
+    for i in (0..n).rev() {
+        let s = (i + 1..n).map(|k| a[k * n + i] * x[k]).sum::<f64>();
+        x[i] = (x[i] - s) / a[i * n + i];
+    }
+
+    Some(x)
+}
+
+/// Invert the symmetric matrix `a` using the sweep operator.
+///
+/// Parameters which are linearly dependent (the pivot falls below `tol` relative to the diagonal)
+/// are not swept, and have zero rows and columns in the result.
+fn invert(mut a: Vec<f64>, n: usize, tol: f64) -> Vec<f64> {
+    let diag = (0..n).map(|j| a[j * n + j]).collect::<Vec<_>>();
+    let mut swept = vec![false; n];
+
+    for k in 0..n {
+        let d = a[k * n + k];
+        if d <= tol * diag[k] || d <= 0.0 {
+            continue;
+        }
+
+        for j in 0..n {
+            a[k * n + j] /= d;
+        }
+        for i in (0..n).filter(|&i| i != k) {
+            let b = a[i * n + k];
+            for j in 0..n {
+                a[i * n + j] -= b * a[k * n + j];
+            }
+            a[i * n + k] = -b / d;
+        }
+        a[k * n + k] = 1.0 / d;
+        swept[k] = true;
+    }
+
+    for i in 0..n {
+        for j in 0..n {
+            if !swept[i] || !swept[j] {
+                a[i * n + j] = 0.0;
+            }
+        }
+    }
+
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inverse() {
+        let a = vec![4.0, 2.0, 0.6, 2.0, 5.0, 1.0, 0.6, 1.0, 3.0];
+        let inv = invert(a.clone(), 3, 1e-14);
+
+        for i in 0..3 {
+            for j in 0..3 {
+                let x = (0..3).map(|k| a[i * 3 + k] * inv[k * 3 + j]).sum::<f64>();
+                let exp = if i == j { 1.0 } else { 0.0 };
+                assert!((x - exp).abs() < 1e-12, "({i}, {j}) = {x}");
+            }
+        }
+
+        // linearly dependent second column
+        let inv = invert(vec![1.0, 2.0, 2.0, 4.0], 2, 1e-14);
+        assert_eq!(inv, vec![1.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn cholesky() {
+        let mut a = [4.0, 2.0, 2.0, 3.0];
+        let x = cholesky_solve(&mut a, 2, &[2.0, 1.0]).unwrap();
+        assert!((x[0] - 0.5).abs() < 1e-12);
+        assert!(x[1].abs() < 1e-12);
+
+        assert!(cholesky_solve(&mut [1.0, 2.0, 2.0, 1.0], 2, &[1.0, 1.0]).is_none());
+    }
+}
@@ -0,0 +1,189 @@
+//! Multi-start search over the parameter space.
+//!
+//! The solver is a local method, it converges to the minimum nearest its starting point.
+//! Refining from many starting points and keeping the best gives more confidence that the global
+//! minimum has been found.
+use super::*;
+use rmpfit::{MPPar, MPStatus};
+
+/// Relative tolerance of the residual sum of squares for a start to be counted as converging to
+/// the best minimum.
+const CONVERGED_RTOL: f64 = 1e-6;
+
+/// The result of a multi-start search.
+pub(super) struct Search {
+    /// The best parameters found.
+    pub params: Vec<f64>,
+    /// The solver status of the best start.
+    pub status: MPStatus,
+    /// Number of starts which converged to the best minimum.
+    pub converged: u64,
+}
+
+/// Generate `n` starting points, the first being `x0`.
+///
+/// Bounded parameters are sampled within their bounds, open sides are sampled within a span
+/// relative to the magnitude of `x0`. Fixed parameters keep their value.
+pub(super) fn starts(
+    x0: &[f64],
+    pars: Option<&[MPPar]>,
+    n: usize,
+    sampling: Sampling,
+    rng: &mut fastrand::Rng,
+) -> Vec<Vec<f64>> {
+    let dflt = MPPar::default();
+    let par = |j: usize| pars.and_then(|p| p.get(j)).unwrap_or(&dflt);
+
+    let ranges = x0
+        .iter()
+        .enumerate()
+        .map(|(j, &x)| {
+            let p = par(j);
+            let span = 10.0 * x.abs().max(1.0);
+            let lo = if p.limited_low { p.limit_low } else { x - span };
+            let hi = if p.limited_up { p.limit_up } else { x + span };
+            // a one sided bound should not shift the span onto the other side
+            let lo = if p.limited_up && !p.limited_low {
+                hi - 2.0 * span
+            } else {
+                lo
+            };
+            let hi = if p.limited_low && !p.limited_up {
+                lo + 2.0 * span
+            } else {
+                hi
+            };
+            (p.fixed, lo, hi)
+        })
+        .collect::<Vec<_>>();
+
+    // unit samples in [0, 1) for each start (after the first) and parameter
+    let m = n.saturating_sub(1);
+    let mut unit = vec![vec![0.0; x0.len()]; m];
+    match sampling {
+        Sampling::Random => unit.iter_mut().flatten().for_each(|u| *u = rng.f64()),
+        Sampling::Lhs => {
+            // each parameter's range is split into m strata, with each stratum sampled once
+            let mut strata = (0..m).collect::<Vec<_>>();
+            for j in 0..x0.len() {
+                rng.shuffle(&mut strata);
+                for (u, &s) in unit.iter_mut().zip(&strata) {
+                    u[j] = (s as f64 + rng.f64()) / m as f64;
+                }
+            }
+        }
+    }
+
+    std::iter::once(x0.to_vec())
+        .chain(unit.into_iter().map(|u| {
+            u.into_iter()
+                .zip(x0)
+                .zip(&ranges)
+                .map(|((u, &x), &(fixed, lo, hi))| if fixed { x } else { lo + u * (hi - lo) })
+                .collect()
+        }))
+        .collect()
+}
+
+/// Run `solve` from each of the starting points, keeping the best by residual sum of squares.
+///
+/// Starts which fail or do not produce a finite residual are skipped. If no start succeeds, the
+/// error of the first start is returned.
+pub(super) fn search<F>(starts: Vec<Vec<f64>>, mut solve: F) -> MPResult<Search>
+where
+    F: FnMut(&mut [f64]) -> MPResult<MPStatus>,
+{
+    let mut err = None;
+    let mut results = Vec::with_capacity(starts.len());
+
+    for mut x in starts {
+        match solve(&mut x) {
+            Ok(s) if s.best_norm.is_finite() => results.push((x, s)),
+            Ok(_) => (),
+            Err(e) => {
+                err.get_or_insert(e);
+            }
+        }
+    }
+
+    let best = results
+        .iter()
+        .map(|(_, s)| s.best_norm)
+        .min_by(f64::total_cmp)
+        .ok_or_else(|| err.unwrap_or(MPError::Nan))?;
+
+    let tol = CONVERGED_RTOL * best + f64::EPSILON;
+    let converged = results
+        .iter()
+        .filter(|(_, s)| s.best_norm - best <= tol)
+        .count() as u64;
+
+    let (params, status) = results
+        .into_iter()
+        .find(|(_, s)| s.best_norm == best)
+        .expect("best exists");
+
+    Ok(Search {
+        params,
+        status,
+        converged,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_within_bounds() {
+        let pars = [
+            MPPar {
+                limited_low: true,
+                limit_low: -1.0,
+                limited_up: true,
+                limit_up: 2.0,
+                ..Default::default()
+            },
+            MPPar {
+                fixed: true,
+                ..Default::default()
+            },
+            MPPar {
+                limited_low: true,
+                limit_low: 5.0,
+                ..Default::default()
+            },
+        ];
+
+        for sampling in [Sampling::Random, Sampling::Lhs] {
+            let mut rng = fastrand::Rng::with_seed(1);
+            let xs = starts(&[0.5, 3.0, 5.0], Some(&pars), 50, sampling, &mut rng);
+            assert_eq!(xs.len(), 50);
+            assert_eq!(xs[0], [0.5, 3.0, 5.0]);
+            for x in &xs {
+                assert!((-1.0..=2.0).contains(&x[0]));
+                assert_eq!(x[1], 3.0);
+                assert!((5.0..=105.0).contains(&x[2]));
+            }
+        }
+    }
+
+    #[test]
+    fn lhs_covers_strata() {
+        let mut rng = fastrand::Rng::with_seed(2);
+        let pars = [MPPar {
+            limited_low: true,
+            limit_low: 0.0,
+            limited_up: true,
+            limit_up: 1.0,
+            ..Default::default()
+        }];
+        let xs = starts(&[0.0], Some(&pars), 11, Sampling::Lhs, &mut rng);
+        let mut strata = xs[1..]
+            .iter()
+            .map(|x| (x[0] * 10.0) as usize)
+            .collect::<Vec<_>>();
+        strata.sort();
+        assert_eq!(strata, (0..10).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,250 @@
+//! Parameter specifications supplied by the user.
+use super::*;
+use rmpfit::MPPar;
+use std::str::FromStr;
+
+/// A parameter constrained within a range.
+///
+/// Parsed from the form `name=low..high`, where either side of the range can be left open.
+///
+/// ```rust
+/// # use fitme::ParamBound;
+/// let b = "k=0..".parse::<ParamBound>().unwrap();
+/// assert_eq!(b.name, "k");
+/// assert_eq!(b.low, Some(0.0));
+/// assert_eq!(b.high, None);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParamBound {
+    /// The parameter name.
+    pub name: String,
+    /// The lower limit, if any.
+    pub low: Option<f64>,
+    /// The upper limit, if any.
+    pub high: Option<f64>,
+}
+
+/// A parameter with a value.
+///
+/// Parsed from the form `name=value`.
+///
+/// ```rust
+/// # use fitme::ParamValue;
+/// let v = "c=3.2".parse::<ParamValue>().unwrap();
+/// assert_eq!(v.name, "c");
+/// assert_eq!(v.value, 3.2);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParamValue {
+    /// The parameter name.
+    pub name: String,
+    /// The parameter value.
+    pub value: f64,
+}
+
+fn split_name(s: &str) -> Result<(&str, &str)> {
+    let (name, x) = s
+        .split_once('=')
+        .ok_or_else(|| miette!("expecting the form `name=...`, found '{}'", s))?;
+    let name = name.trim();
+    ensure!(!name.is_empty(), "parameter name is empty in '{}'", s);
+    Ok((name, x.trim()))
+}
+
+fn parse_num(s: &str) -> Result<f64> {
+    s.parse::<f64>()
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to parse '{s}' as number"))
+}
+
+impl FromStr for ParamBound {
+    type Err = miette::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, range) = split_name(s)?;
+        let (low, high) = range
+            .split_once("..")
+            .ok_or_else(|| miette!("expecting a range `low..high`, found '{}'", range))?;
+
+        let opt = |x: &str| {
+            let x = x.trim();
+            if x.is_empty() {
+                Ok(None)
+            } else {
+                parse_num(x).map(Some)
+            }
+        };
+
+        let low = opt(low)?;
+        let high = opt(high)?;
+
+        if let (Some(l), Some(h)) = (low, high) {
+            ensure!(
+                l <= h,
+                "lower bound {} is greater than upper bound {} for '{}'",
+                l,
+                h,
+                name
+            );
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            low,
+            high,
+        })
+    }
+}
+
+impl FromStr for ParamValue {
+    type Err = miette::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, x) = split_name(s)?;
+        Ok(Self {
+            name: name.to_string(),
+            value: parse_num(x)?,
+        })
+    }
+}
+
+/// Find the index of the parameter `name` in `params`.
+fn param_idx(params: &[String], name: &str) -> Result<usize> {
+    params
+        .iter()
+        .position(|p| p == name)
+        .ok_or_else(|| miette!("parameter '{}' not found in equation", name))
+        .wrap_err_with(|| format!("equation parameters: {}", params.join(", ")))
+}
+
+impl FitOptions {
+    /// These options with the parameter bounds, fixed values and initial values restricted to
+    /// the parameters in `params`.
+    pub(crate) fn restricted(&self, params: &[String]) -> FitOptions {
+        let has = |name: &String| params.contains(name);
+        FitOptions {
+            bounds: self
+                .bounds
+                .iter()
+                .filter(|x| has(&x.name))
+                .cloned()
+                .collect(),
+            fixed: self
+                .fixed
+                .iter()
+                .filter(|x| has(&x.name))
+                .cloned()
+                .collect(),
+            init: self.init.iter().filter(|x| has(&x.name)).cloned().collect(),
+            ..self.clone()
+        }
+    }
+}
+
+/// The indices and values of the user supplied initial parameter values.
+pub(super) fn initial_values(params: &[String], opts: &FitOptions) -> Result<Vec<(usize, f64)>> {
+    opts.init
+        .iter()
+        .map(|v| param_idx(params, &v.name).map(|i| (i, v.value)))
+        .collect()
+}
+
+/// Per-parameter constraints.
+pub(super) struct Constraints {
+    /// The constraints passed to the solver.
+    pub pars: Vec<MPPar>,
+    /// The fixed parameter indices and values.
+    fixed: Vec<(usize, f64)>,
+}
+
+impl Constraints {
+    /// Build the per-parameter constraints from the options.
+    ///
+    /// Returns `None` if no parameter is constrained.
+    pub fn new(params: &[String], opts: &FitOptions) -> Result<Option<Self>> {
+        if opts.bounds.is_empty() && opts.fixed.is_empty() {
+            return Ok(None);
+        }
+
+        let mut pars = params.iter().map(|_| MPPar::default()).collect::<Vec<_>>();
+
+        for b in &opts.bounds {
+            let p = &mut pars[param_idx(params, &b.name)?];
+            if let Some(x) = b.low {
+                p.limited_low = true;
+                p.limit_low = x;
+            }
+            if let Some(x) = b.high {
+                p.limited_up = true;
+                p.limit_up = x;
+            }
+        }
+
+        let mut fixed = Vec::with_capacity(opts.fixed.len());
+        for f in &opts.fixed {
+            let i = param_idx(params, &f.name)?;
+            let p = &mut pars[i];
+            p.fixed = true;
+            // rmpfit checks fixed values against the limits, regardless of whether limited
+            p.limit_low = f.value;
+            p.limit_up = f.value;
+            fixed.push((i, f.value));
+        }
+
+        Ok(Some(Self { pars, fixed }))
+    }
+
+    /// Clamp the parameters into their bounds and set the fixed parameter values.
+    pub fn apply(&self, x: &mut [f64]) {
+        for (x, p) in x.iter_mut().zip(&self.pars) {
+            if p.limited_low {
+                *x = x.max(p.limit_low);
+            }
+            if p.limited_up {
+                *x = x.min(p.limit_up);
+            }
+        }
+
+        // fixed values are set last, they are not subject to bounds
+        for &(i, v) in &self.fixed {
+            x[i] = v;
+        }
+    }
+
+    /// Is the parameter at index `i` fixed?
+    pub fn is_fixed(&self, i: usize) -> bool {
+        self.pars[i].fixed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_bounds() {
+        let p = |s: &str| s.parse::<ParamBound>().map(|b| (b.name, b.low, b.high));
+
+        assert_eq!(p("k=0..").unwrap(), ("k".into(), Some(0.0), None));
+        assert_eq!(p("k=..5").unwrap(), ("k".into(), None, Some(5.0)));
+        assert_eq!(
+            p(" k = -1.5..2.5 ").unwrap(),
+            ("k".into(), Some(-1.5), Some(2.5))
+        );
+        assert_eq!(p("k=..").unwrap(), ("k".into(), None, None));
+        assert!(p("k=0").is_err());
+        assert!(p("=0..1").is_err());
+        assert!(p("k=1..0").is_err());
+        assert!(p("k=a..1").is_err());
+    }
+
+    #[test]
+    fn parse_values() {
+        let p = |s: &str| s.parse::<ParamValue>().map(|v| (v.name, v.value));
+
+        assert_eq!(p("c=3.2").unwrap(), ("c".into(), 3.2));
+        assert_eq!(p("c = -1e3").unwrap(), ("c".into(), -1e3));
+        assert!(p("c").is_err());
+        assert!(p("c=").is_err());
+    }
+}
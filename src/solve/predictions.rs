@@ -0,0 +1,425 @@
+//! Per-observation predictions and residual diagnostics.
+use super::*;
+use crate::data::Cell;
+
+/// The prediction of a single observation, see [`Fit::predictions`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Prediction {
+    /// The value predicted by the fitted equation.
+    pub predicted: f64,
+
+    /// The residual, the observed less the predicted value.
+    pub residual: f64,
+
+    /// The residual divided by its estimated standard deviation, accounting for the observation's
+    /// weight and leverage.
+    pub standardised_residual: f64,
+
+    /// The leverage (hat value) of the observation, how strongly it pulls the fit towards itself.
+    pub leverage: f64,
+}
+
+/// The prediction of a new observation, see [`Fit::forecast`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Forecast {
+    /// The value predicted by the fitted equation.
+    pub predicted: f64,
+
+    /// The standard error of the predicted (mean) value, from the parameter covariance.
+    pub standard_error: f64,
+
+    /// The lower limit of the confidence band, the uncertainty of the predicted (mean) value.
+    pub confidence_lower: f64,
+
+    /// The upper limit of the confidence band.
+    pub confidence_upper: f64,
+
+    /// The lower limit of the prediction band, the uncertainty of a single new observation.
+    pub prediction_lower: f64,
+
+    /// The upper limit of the prediction band.
+    pub prediction_upper: f64,
+}
+
+impl Fit {
+    /// Predict each observation in `data` with the fitted parameters, along with residual
+    /// diagnostics.
+    ///
+    /// `eq` must be the equation which was fitted, and `data` is expected to be the data it was
+    /// fitted against (leverages and standardised residuals are relative to the fit).
+    pub fn predictions<E: Equation>(
+        &self,
+        eq: &E,
+        data: &Data,
+        target: &str,
+    ) -> Result<Vec<Prediction>> {
+        self.check_equation(eq)?;
+
+        let tgt = data
+            .headers()
+            .find_ignore_case_and_ws(target)
+            .ok_or_else(|| miette!("could not find column '{}' in headers", target))
+            .wrap_err_with(|| data::match_hdr_help(data.headers(), target))?;
+        let y = data
+            .num_col(tgt)
+            .ok_or_else(|| miette!("column index {} not in table", tgt))??;
+
+        let n = data.len();
+        if let Some(w) = &self.weights {
+            ensure!(
+                w.len() == n,
+                "the fit has {} weights but the data has {} rows",
+                w.len(),
+                n
+            );
+        }
+
+        let params = &self.parameter_values;
+        let mut pred = vec![0.0; n];
+        eq.solve_batch(params, data, &mut pred)
+            .ok_or_else(|| miette!("failed to solve equation when predicting"))?;
+
+        let jac = jacobian(eq, params, data)?;
+
+        let np = params.len();
+        let s2 = self.rmsr * self.rmsr;
+
+        let preds = (0..n)
+            .map(|i| {
+                let w = self.weights.as_ref().map_or(1.0, |w| w[i]);
+                // h = w * J C J' / s^2, where C is the scaled covariance
+                let leverage = w * self.mean_variance(&jac[i * np..(i + 1) * np]) / s2;
+                let residual = y[i] - pred[i];
+                let standardised_residual =
+                    w.sqrt() * residual / (self.rmsr * (1.0 - leverage).sqrt());
+
+                Prediction {
+                    predicted: pred[i],
+                    residual,
+                    standardised_residual,
+                    leverage,
+                }
+            })
+            .collect();
+
+        Ok(preds)
+    }
+
+    /// Predict each row in `data` with the fitted parameters, along with confidence and
+    /// prediction bands at the confidence level `level`.
+    ///
+    /// `eq` must be the equation which was fitted, but `data` can be new observations.
+    /// The bands are derived from the parameter covariance, the prediction band includes the
+    /// residual variance of a single (unweighted) observation.
+    pub fn forecast<E: Equation>(&self, eq: &E, data: &Data, level: f64) -> Result<Vec<Forecast>> {
+        self.check_equation(eq)?;
+        ensure!(
+            level > 0.0 && level < 1.0,
+            "confidence level must be between 0 and 1, found {}",
+            level
+        );
+
+        let n = data.len();
+        let np = self.parameter_values.len();
+        ensure!(
+            self.covariance.len() == np,
+            "the fit is missing the parameter covariance"
+        );
+
+        let mut pred = vec![0.0; n];
+        eq.solve_batch(&self.parameter_values, data, &mut pred)
+            .ok_or_else(|| miette!("failed to solve equation when predicting"))?;
+
+        let jac = jacobian(eq, &self.parameter_values, data)?;
+
+        // fits saved before the residual degrees of freedom were recorded derive them
+        let free = np - self.fixed_parameters.len();
+        let dfr = match self.dfr.is_nan() {
+            true => self.n as f64 - free as f64,
+            false => self.dfr,
+        };
+        let t = crate::stats::t_quantile(0.5 + level / 2.0, dfr);
+        let s2 = self.rmsr * self.rmsr;
+
+        let fcs = (0..n)
+            .map(|i| {
+                let predicted = pred[i];
+                let var = self.mean_variance(&jac[i * np..(i + 1) * np]);
+                let se = var.sqrt();
+                let pe = (var + s2).sqrt();
+
+                Forecast {
+                    predicted,
+                    standard_error: se,
+                    confidence_lower: predicted - t * se,
+                    confidence_upper: predicted + t * se,
+                    prediction_lower: predicted - t * pe,
+                    prediction_upper: predicted + t * pe,
+                }
+            })
+            .collect();
+
+        Ok(fcs)
+    }
+
+    fn check_equation<E: Equation>(&self, eq: &E) -> Result<()> {
+        ensure!(
+            eq.params() == self.parameter_names,
+            "equation parameters ({}) do not match the fitted parameters ({})",
+            eq.params().join(", "),
+            self.parameter_names.join(", ")
+        );
+        Ok(())
+    }
+
+    /// The variance of a predicted value `J C J'`, where `j` is the row of the Jacobian.
+    fn mean_variance(&self, j: &[f64]) -> f64 {
+        let np = j.len();
+        (0..np)
+            .map(|a| {
+                (0..np)
+                    .map(|b| j[a] * self.covariance[a][b] * j[b])
+                    .sum::<f64>()
+            })
+            .sum()
+    }
+}
+
+/// The row-major Jacobian of the equation, falling back to central differences if the equation
+/// does not supply derivatives.
+fn jacobian<E: Equation>(eq: &E, params: &[f64], data: &Data) -> Result<Vec<f64>> {
+    let n = data.len();
+    let np = params.len();
+    let mut jac = vec![0.0; n * np];
+
+    if eq.jacobian_batch(params, data, &mut jac).is_some() {
+        return Ok(jac);
+    }
+
+    let mut x = params.to_vec();
+    let mut hi = vec![0.0; n];
+    let mut lo = vec![0.0; n];
+    for j in 0..np {
+        let h = f64::EPSILON.cbrt() * params[j].abs().max(1.0);
+        x[j] = params[j] + h;
+        let a = eq.solve_batch(&x, data, &mut hi);
+        x[j] = params[j] - h;
+        let b = eq.solve_batch(&x, data, &mut lo);
+        x[j] = params[j];
+        a.and(b)
+            .ok_or_else(|| miette!("failed to solve equation when differencing"))?;
+
+        for i in 0..n {
+            jac[i * np + j] = (hi[i] - lo[i]) / (2.0 * h);
+        }
+    }
+
+    Ok(jac)
+}
+
+/// The columns of [`Prediction::values`].
+pub(crate) const PREDICTION_COLUMNS: [&str; 4] =
+    ["predicted", "residual", "standardised_residual", "leverage"];
+
+/// The columns of [`Forecast::values`], without bands.
+pub(crate) const FORECAST_COLUMNS: [&str; 1] = ["predicted"];
+
+/// The columns of [`Forecast::values`], with bands.
+pub(crate) const FORECAST_BAND_COLUMNS: [&str; 6] = [
+    "predicted",
+    "standard_error",
+    "confidence_lower",
+    "confidence_upper",
+    "prediction_lower",
+    "prediction_upper",
+];
+
+impl Prediction {
+    pub(crate) fn values(&self) -> Vec<f64> {
+        let Prediction {
+            predicted,
+            residual,
+            standardised_residual,
+            leverage,
+        } = *self;
+        vec![predicted, residual, standardised_residual, leverage]
+    }
+}
+
+impl Forecast {
+    pub(crate) fn values(&self, bands: bool) -> Vec<f64> {
+        let Forecast {
+            predicted,
+            standard_error,
+            confidence_lower,
+            confidence_upper,
+            prediction_lower,
+            prediction_upper,
+        } = *self;
+        if bands {
+            vec![
+                predicted,
+                standard_error,
+                confidence_lower,
+                confidence_upper,
+                prediction_lower,
+                prediction_upper,
+            ]
+        } else {
+            vec![predicted]
+        }
+    }
+}
+
+/// Write the data rows alongside extra columns of values, as CSV or a JSON array of objects.
+pub(crate) fn write_rows(
+    data: &Data,
+    columns: &[&str],
+    values: &[Vec<f64>],
+    json: bool,
+    wtr: impl Write,
+) -> Result<()> {
+    if json {
+        write_rows_json(data, columns, values, wtr)
+    } else {
+        write_rows_csv(data, columns, values, wtr).into_diagnostic()
+    }
+}
+
+fn write_rows_csv(
+    data: &Data,
+    columns: &[&str],
+    values: &[Vec<f64>],
+    wtr: impl Write,
+) -> io::Result<()> {
+    let mut w = csv::Writer::from_writer(wtr);
+
+    w.write_record(data.headers().iter().chain(columns.iter().copied()))?;
+
+    for (row, xs) in data.rows().zip(values) {
+        for c in 0..data.headers().len() {
+            match row.get(c).expect("inside data") {
+                Cell::Num(x) => w.write_field(x.to_string())?,
+                Cell::Txt(x) => w.write_field(x)?,
+            }
+        }
+        for x in xs {
+            w.write_field(x.to_string())?;
+        }
+        w.write_record(None::<&[u8]>)?;
+    }
+
+    w.flush()
+}
+
+/// Write the rows as an array of objects, keeping the column order (`serde_json` is built with
+/// `preserve_order`).
+fn write_rows_json(
+    data: &Data,
+    columns: &[&str],
+    values: &[Vec<f64>],
+    wtr: impl Write,
+) -> Result<()> {
+    use serde_json::{Map, Value};
+
+    let rows = data
+        .rows()
+        .zip(values)
+        .map(|(row, xs)| {
+            let mut obj = data
+                .headers()
+                .iter()
+                .enumerate()
+                .map(|(c, h)| {
+                    let v = match row.get(c).expect("inside data") {
+                        Cell::Num(x) => Value::from(x),
+                        Cell::Txt(x) => Value::from(x),
+                    };
+                    (h.to_string(), v)
+                })
+                .collect::<Map<_, _>>();
+            for (h, &x) in columns.iter().zip(xs) {
+                obj.insert(h.to_string(), Value::from(x));
+            }
+            Value::Object(obj)
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::to_writer(wtr, &rows).into_diagnostic()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::v1::Eq;
+
+    #[test]
+    fn linear_hat_values() {
+        let xs = [0.0, 1.0, 2.0, 3.0, 4.0, 7.0];
+        let ys = [0.3, 1.9, 4.2, 5.8, 8.1, 14.2];
+        let data = Data::new(
+            Headers::from_iter(["x", "y"]),
+            xs.iter().zip(ys).map(|(&x, y)| [x, y]),
+        )
+        .unwrap();
+        let eq = Eq::parse("m * x + c", data.headers()).unwrap();
+        let fit = fit_with(&eq, &data, "y", &FitOptions::default()).unwrap();
+
+        let preds = fit.predictions(&eq, &data, "y").unwrap();
+
+        // leverage of simple linear regression is 1/n + (x - mean)^2 / Sxx
+        let n = xs.len() as f64;
+        let mean = xs.iter().sum::<f64>() / n;
+        let sxx = xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>();
+        for (x, p) in xs.iter().zip(&preds) {
+            let h = 1.0 / n + (x - mean).powi(2) / sxx;
+            assert!((p.leverage - h).abs() < 1e-9, "{} != {h}", p.leverage);
+        }
+
+        let h = preds.iter().map(|p| p.leverage).sum::<f64>();
+        assert!((h - 2.0).abs() < 1e-9);
+
+        for (y, p) in ys.iter().zip(&preds) {
+            assert!((p.predicted + p.residual - y).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn forecast_bands() {
+        let data = Data::new(
+            Headers::from_iter(["x", "y"]),
+            [[0.0, 0.3], [1.0, 1.9], [2.0, 4.2], [3.0, 5.8], [4.0, 8.1]],
+        )
+        .unwrap();
+        let eq = Eq::parse("m * x + c", data.headers()).unwrap();
+        let opts = FitOptions {
+            fixed: vec!["c=0.2".parse().unwrap()],
+            ..Default::default()
+        };
+        let fit = fit_with(&eq, &data, "y", &opts).unwrap();
+
+        // round trip through JSON, where the fixed parameter's statistics are null
+        let json = serde_json::to_string(&fit).unwrap();
+        assert!(json.contains("null"));
+        let fit: Fit = serde_json::from_str(&json).unwrap();
+        assert!(fit.tvals[0].is_nan());
+
+        let new = Data::new(Headers::from_iter(["x"]), [[2.0], [10.0]]).unwrap();
+        let eq = Eq::parse("m * x + c", new.headers()).unwrap();
+        let fcs = fit.forecast(&eq, &new, 0.95).unwrap();
+
+        let m = fit.parameter_values[1];
+        assert_eq!(fit.dfr, 4.0);
+        let t = crate::stats::t_quantile(0.975, 4.0);
+        for (x, f) in [2.0, 10.0].into_iter().zip(&fcs) {
+            assert!((f.predicted - (m * x + 0.2)).abs() < 1e-12);
+            // only the slope varies, so the standard error is proportional to x
+            assert!((f.standard_error - x * fit.xerrs[1]).abs() < 1e-12);
+            assert!((f.confidence_upper - f.predicted - t * f.standard_error).abs() < 1e-12);
+            assert!(f.prediction_lower < f.confidence_lower);
+            assert!(f.prediction_upper > f.confidence_upper);
+        }
+
+        assert!(fit.forecast(&eq, &new, 1.5).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! Robust losses, fitted with iteratively reweighted least squares (IRLS).
+//!
+//! Each round, the residuals are standardised by a robust estimate of their scale (the median
+//! absolute residual) and each observation is weighted by how much the loss discounts its
+//! residual. The least squares fit is then repeated with these weights, until the parameters
+//! settle.
+use super::*;
+
+/// Maximum number of reweighting rounds.
+pub(super) const MAX_ROUNDS: usize = 100;
+
+/// Relative change in the parameters below which the reweighting has converged.
+const PARAM_RTOL: f64 = 1e-8;
+
+/// Scales the median absolute deviation to a consistent estimator of the standard deviation of
+/// normally distributed residuals.
+const MAD_SCALE: f64 = 1.4826;
+
+/// The default tuning constant of each loss, in standardised residual units.
+///
+/// These give 95% asymptotic efficiency when the residuals are normally distributed.
+pub(super) fn tuning(loss: Loss) -> f64 {
+    match loss {
+        Loss::Squared => f64::INFINITY,
+        Loss::Huber => 1.345,
+        Loss::SoftL1 => 1.287,
+        Loss::Cauchy => 2.385,
+        Loss::Tukey => 4.685,
+    }
+}
+
+/// The IRLS weight of a residual `u`, which is scaled by the tuning constant.
+fn weight(loss: Loss, u: f64) -> f64 {
+    let u = u.abs();
+    match loss {
+        Loss::Squared => 1.0,
+        Loss::Huber if u <= 1.0 => 1.0,
+        Loss::Huber => 1.0 / u,
+        Loss::SoftL1 => 1.0 / (1.0 + u * u).sqrt(),
+        Loss::Cauchy => 1.0 / (1.0 + u * u),
+        Loss::Tukey if u < 1.0 => (1.0 - u * u).powi(2),
+        Loss::Tukey => 0.0,
+    }
+}
+
+fn median(xs: &mut [f64]) -> f64 {
+    xs.sort_by(f64::total_cmp);
+    let n = xs.len();
+    if n == 0 {
+        f64::NAN
+    } else if n % 2 == 1 {
+        xs[n / 2]
+    } else {
+        (xs[n / 2 - 1] + xs[n / 2]) / 2.0
+    }
+}
+
+/// Calculate the observation weights for the residuals `resid`.
+///
+/// `base` are any user supplied weights, which the robust weights are combined with.
+/// Returns `None` if the residual scale is zero (a perfect fit), in which case no reweighting is
+/// needed.
+pub(super) fn weights(loss: Loss, c: f64, resid: &[f64], base: Option<&[f64]>) -> Option<Vec<f64>> {
+    let base = |i: usize| base.map_or(1.0, |w| w[i]);
+
+    let r = resid
+        .iter()
+        .enumerate()
+        .map(|(i, r)| r * base(i).sqrt())
+        .collect::<Vec<_>>();
+
+    // the residuals are centred on zero
+    let mut dev = r.iter().map(|r| r.abs()).collect::<Vec<_>>();
+    let scale = MAD_SCALE * median(&mut dev);
+
+    if !scale.is_normal() {
+        return None;
+    }
+
+    let w = r
+        .into_iter()
+        .enumerate()
+        .map(|(i, r)| base(i) * weight(loss, r / (c * scale)))
+        .collect();
+
+    Some(w)
+}
+
+/// Have the parameters settled between rounds?
+pub(super) fn converged(prev: &[f64], next: &[f64]) -> bool {
+    prev.iter()
+        .zip(next)
+        .all(|(a, b)| (a - b).abs() <= PARAM_RTOL * a.abs().max(b.abs()).max(f64::EPSILON))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loss_weights() {
+        assert_eq!(weight(Loss::Squared, 10.0), 1.0);
+        assert_eq!(weight(Loss::Huber, 0.5), 1.0);
+        assert_eq!(weight(Loss::Huber, -4.0), 0.25);
+        assert_eq!(weight(Loss::Cauchy, 1.0), 0.5);
+        assert_eq!(weight(Loss::SoftL1, 0.0), 1.0);
+        assert!((weight(Loss::SoftL1, 3.0) - 1.0 / 10f64.sqrt()).abs() < 1e-15);
+        assert_eq!(weight(Loss::Tukey, 0.5), 0.5625);
+        assert_eq!(weight(Loss::Tukey, 1.5), 0.0);
+    }
+
+    #[test]
+    fn outliers_downweighted() {
+        let r = [0.1, -0.2, 0.05, -0.1, 0.15, 50.0];
+        let w = weights(Loss::Huber, 1.345, &r, None).unwrap();
+        assert!(w[..5].iter().all(|&w| w == 1.0));
+        assert!(w[5] < 0.01);
+
+        let w = weights(Loss::Tukey, 4.685, &r, None).unwrap();
+        assert_eq!(w[5], 0.0);
+
+        assert!(weights(Loss::Huber, 1.345, &[0.0; 4], None).is_none());
+    }
+}
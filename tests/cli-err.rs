@@ -19,6 +19,20 @@ For more information, try \'--help\'.
     );
 }
 
+#[test]
+fn predict_no_args() {
+    cmd().arg("predict").assert().failure().stderr(
+        "\
+error: the following required arguments were not provided:
+  <FIT>
+
+Usage: fitme predict <FIT> [DATA]
+
+For more information, try \'--help\'.
+",
+    );
+}
+
 #[test]
 fn target_not_found() {
     cmd()
@@ -79,14 +93,25 @@ fn matching_column_name() {
         .success()
         .stdout(
             "\
-───────────────────────────────────────────────
- Parameter   Value    Standard Error   t-value 
-═══════════════════════════════════════════════
- FOO         -1.024            1.641    -0.624 
-───────────────────────────────────────────────
+───────────────────────────────────────────────────────────────────────────────────────
+ Parameter   Value    Standard Error   t-value   p-value   95% CI Lower   95% CI Upper 
+═══════════════════════════════════════════════════════════════════════════════════════
+ FOO         -1.024            1.547    -0.661     0.524         -4.525          2.476 
+───────────────────────────────────────────────────────────────────────────────────────
   Number of observations: 10.0
-  Root Mean Squared Residual error: 5.191
-  R-sq Adjusted: 0.243
+  Residual degrees of freedom: 9.0
+  Sum of Squared Residuals: 215.6
+  Root Mean Squared Residual error: 4.894
+  Reduced Chi-sq: 23.95
+  R-sq: 0.327
+  R-sq Adjusted: 0.327
+  F-statistic: NaN
+  F-statistic p-value: NaN
+  Log-likelihood: -29.54
+  AIC: 63.08
+  AICc: 64.80
+  BIC: 63.69
+  Solver: 2 iterations, 2 function evaluations, convergence in orthogonality
 ",
         );
 }
@@ -168,3 +193,352 @@ Error:   × in \'tests/file1.csv\'
 ",
         );
 }
+
+#[test]
+fn unknown_fixed_parameter() {
+    cmd()
+        .args(["y", "m * x + c", "tests/file1.csv", "--fix", "k=3"])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/file1.csv\'
+  ├─▶ equation parameters: c, m
+  ╰─▶ parameter \'k\' not found in equation
+
+",
+        );
+}
+
+#[test]
+fn inverted_bound() {
+    cmd()
+        .args(["y", "m * x + c", "tests/file1.csv", "--bound", "m=2..1"])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+error: invalid value \'m=2..1\' for \'--bound <PARAM=LOW..HIGH>\': lower bound 2 is greater than upper bound 1 for \'m\'
+
+For more information, try \'--help\'.
+",
+        );
+}
+
+#[test]
+fn negative_sigma() {
+    cmd()
+        .args(["y", "m * x + c", "tests/file1.csv", "--sigma", "x"])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/file1.csv\'
+  ├─▶ in weights column \'x\'
+  ╰─▶ sigma must be positive, found -1.7237128 in row index 1
+
+",
+        );
+}
+
+#[test]
+fn confidence_out_of_range() {
+    cmd()
+        .args(["y", "m * x + c", "tests/file1.csv", "--confidence", "1"])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/file1.csv\'
+  ╰─▶ confidence level must be between 0 and 1, found 1
+
+",
+        );
+}
+
+#[test]
+fn predict_mismatched_equation() {
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args([
+            "predict",
+            "tests/file1-fit.json",
+            "tests/file1.csv",
+            "--expr",
+            "a * x + b",
+        ])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/file1.csv\'
+  ╰─▶ equation parameters (a, b) do not match the fitted parameters (c, m)
+
+",
+        );
+}
+
+#[test]
+fn compare_unknown_parameter() {
+    cmd()
+        .args([
+            "y",
+            "m * x + c",
+            "tests/file1.csv",
+            "--compare=c",
+            "--fix=k=1",
+        ])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/file1.csv\'
+  ╰─▶ parameter \'k\' not found in any equation
+
+",
+        );
+}
+
+#[test]
+fn compare_conflicts() {
+    // the comparison does not show bootstrap estimates, and always shows the statistics
+    for arg in ["--bootstrap=10", "--no-stats"] {
+        cmd()
+            .args(["y", "m * x + c", "tests/file1.csv", "--compare=m * x", arg])
+            .assert()
+            .failure()
+            .stderr(format!(
+                "\
+error: the argument \'--compare <EXPR>\' cannot be used with \'{}\'
+
+Usage: fitme --compare <EXPR> <TARGET> <EXPR> <DATA>
+
+For more information, try \'--help\'.
+",
+                arg.replace("=10", " <N>")
+            ));
+    }
+}
+
+#[test]
+fn too_many_folds() {
+    cmd()
+        .args(["y", "m * x + c", "tests/file1.csv", "--cv=10"])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/file1.csv\'
+  ╰─▶ number of folds must be between 2 and half the number of observations
+      (10), found 10
+
+",
+        );
+}
+
+#[test]
+fn group_by_unknown_column() {
+    cmd()
+        .args(["y", "m * x + c", "tests/file1.csv", "--group-by=z"])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/file1.csv\'
+  ├─▶ help - no columns match, use `cat <file> | head -n1` for inspect headers
+  ╰─▶ could not find column \'z\' in headers
+
+",
+        );
+}
+
+#[test]
+fn categorical_without_parameter() {
+    cmd()
+        .args([
+            "sepalLength",
+            "a * class + sqrt(class)",
+            "tests/iris.csv",
+            "--categorical=class",
+        ])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/iris.csv\'
+  ├─▶ in expr: a * class + sqrt(class)
+  ╰─▶ categorical column \'class\' must multiply a term with parameters, eg `a
+      * class`
+
+",
+        );
+}
+
+#[test]
+fn text_column_not_categorical() {
+    cmd()
+        .args(["sepalLength", "a * class + c", "tests/iris.csv"])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/iris.csv\'
+  ├─▶ in row index 1
+  ├─▶ in column index 4
+  ╰─▶ failed to parse \'Iris-setosa\' as number
+
+",
+        );
+}
+
+#[test]
+fn categorical_not_text() {
+    cmd()
+        .args(["y", "m * x + c", "tests/file1.csv", "--categorical=x"])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/file1.csv\'
+  ├─▶ in row index 1
+  ╰─▶ categorical column \'x\' must be text
+
+",
+        );
+}
+
+#[test]
+fn filter_unknown_column() {
+    cmd()
+        .args(["y", "m * x + c", "tests/file1.csv", "--where", "x > k"])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/file1.csv\'
+  ├─▶ in filter: x > k
+  ╰─▶ unknown columns in filter: k
+
+",
+        );
+}
+
+#[test]
+fn filter_every_row() {
+    cmd()
+        .args(["y", "m * x + c", "tests/file1.csv", "--where", "x > 100"])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Filtered out 10 of 10 rows
+Error:   × in \'tests/file1.csv\'
+  ╰─▶ every row was filtered out
+
+",
+        );
+}
+
+#[test]
+fn derived_column_exists() {
+    cmd()
+        .args(["y", "m * x + c", "tests/file1.csv", "--let", "X = x^2"])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/file1.csv\'
+  ╰─▶ column \'X\' already exists
+
+",
+        );
+}
+
+#[test]
+fn missing_value() {
+    cmd()
+        .args(["y", "m * x + c", "tests/missing.csv"])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/missing.csv\'
+  ├─▶ help - use `--missing drop` to drop rows with missing values
+  ├─▶ in row index 2
+  ╰─▶ missing value in column \'y\'
+
+",
+        );
+
+    // filter columns are checked
+    cmd()
+        .args([
+            "y",
+            "m * x + c",
+            "tests/missing.csv",
+            "--where",
+            "note != bad",
+        ])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/missing.csv\'
+  ├─▶ help - use `--missing drop` to drop rows with missing values
+  ├─▶ in row index 1
+  ╰─▶ missing value in column \'note\'
+
+",
+        );
+
+    // only the default tokens are replaced
+    cmd()
+        .args(["y", "m * x + c", "tests/missing.csv", "--na", "-"])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/missing.csv\'
+  ├─▶ in row index 2
+  ├─▶ in column index 1
+  ╰─▶ failed to parse \'NA\' as number
+
+",
+        );
+}
+
+#[test]
+fn csv_dialect() {
+    cmd()
+        .args(["y", "m * x + c", "tests/file1.csv", "--delimiter", ";;"])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+error: invalid value \';;\' for \'--delimiter <CHAR>\': expecting a single ASCII character, found \';;\'
+
+For more information, try \'--help\'.
+",
+        );
+
+    cmd()
+        .args(["y", "m * x + c", "tests/file1.csv", "--skip-rows", "20"])
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Error:   × in \'tests/file1.csv\'
+  ╰─▶ cannot skip 20 rows, the input has 11 lines
+
+",
+        );
+}
+
+#[test]
+fn predict_fit_without_equation() {
+    cmd()
+        .args(["predict", "tests/file1-fit.json", "tests/file1.csv"])
+        .assert()
+        .failure()
+        .stderr("Error:   × the fit does not store its equation, use `--expr`\n\n");
+}
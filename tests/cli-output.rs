@@ -2,7 +2,7 @@ use assert_cmd::Command;
 
 fn cmd() -> Command {
     let mut c = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
-    c.args(&["y", "m * x + c", "tests/file1.csv"]);
+    c.args(["y", "m * x + c", "tests/file1.csv"]);
     c
 }
 
@@ -10,28 +10,39 @@ fn cmd() -> Command {
 fn vanilla() {
     cmd().assert().success().stdout(
         "\
-──────────────────────────────────────────────
- Parameter   Value   Standard Error   t-value 
-══════════════════════════════════════════════
- c           3.209            0.013     230.3 
-──────────────────────────────────────────────
- m           1.770            0.011     149.0 
-──────────────────────────────────────────────
+────────────────────────────────────────────────────────────────────────────────────────
+ Parameter   Value   Standard Error   t-value   p-value     95% CI Lower   95% CI Upper 
+════════════════════════════════════════════════════════════════════════════════════════
+ c           3.209            0.013     246.2   8.286e-17          3.179          3.240 
+────────────────────────────────────────────────────────────────────────────────────────
+ m           1.770            0.011     159.3   2.695e-15          1.745          1.796 
+────────────────────────────────────────────────────────────────────────────────────────
   Number of observations: 10.0
-  Root Mean Squared Residual error: 0.043
+  Residual degrees of freedom: 8.0
+  Sum of Squared Residuals: 0.013
+  Root Mean Squared Residual error: 0.041
+  Reduced Chi-sq: 0.001
+  R-sq: 0.999
   R-sq Adjusted: 0.999
+  F-statistic: 25.38 K
+  F-statistic p-value: 2.695e-15
+  Log-likelihood: 18.84
+  AIC: -31.69
+  AICc: -27.69
+  BIC: -30.78
+  Solver: 2 iterations, 2 function evaluations, convergence in orthogonality
 ",
     );
 
     cmd().arg("--no-stats").assert().success().stdout(
         "\
-──────────────────────────────────────────────
- Parameter   Value   Standard Error   t-value 
-══════════════════════════════════════════════
- c           3.209            0.013     230.3 
-──────────────────────────────────────────────
- m           1.770            0.011     149.0 
-──────────────────────────────────────────────
+────────────────────────────────────────────────────────────────────────────────────────
+ Parameter   Value   Standard Error   t-value   p-value     95% CI Lower   95% CI Upper 
+════════════════════════════════════════════════════════════════════════════════════════
+ c           3.209            0.013     246.2   8.286e-17          3.179          3.240 
+────────────────────────────────────────────────────────────────────────────────────────
+ m           1.770            0.011     159.3   2.695e-15          1.745          1.796 
+────────────────────────────────────────────────────────────────────────────────────────
 ",
     );
 }
@@ -39,12 +50,23 @@ fn vanilla() {
 #[test]
 fn plain() {
     cmd().arg("-o=plain").assert().success().stdout(
-        " Parameter  Value  Standard Error  t-value 
- c          3.209           0.013    230.3 
- m          1.770           0.011    149.0 
+        " Parameter  Value  Standard Error  t-value  p-value    95% CI Lower  95% CI Upper 
+ c          3.209           0.013    246.2  8.286e-17         3.179         3.240 
+ m          1.770           0.011    159.3  2.695e-15         1.745         1.796 
   Number of observations: 10.0
-  Root Mean Squared Residual error: 0.043
+  Residual degrees of freedom: 8.0
+  Sum of Squared Residuals: 0.013
+  Root Mean Squared Residual error: 0.041
+  Reduced Chi-sq: 0.001
+  R-sq: 0.999
   R-sq Adjusted: 0.999
+  F-statistic: 25.38 K
+  F-statistic p-value: 2.695e-15
+  Log-likelihood: 18.84
+  AIC: -31.69
+  AICc: -27.69
+  BIC: -30.78
+  Solver: 2 iterations, 2 function evaluations, convergence in orthogonality
 ",
     );
 
@@ -54,9 +76,9 @@ fn plain() {
         .assert()
         .success()
         .stdout(
-            " Parameter  Value  Standard Error  t-value 
- c          3.209           0.013    230.3 
- m          1.770           0.011    149.0 
+            " Parameter  Value  Standard Error  t-value  p-value    95% CI Lower  95% CI Upper 
+ c          3.209           0.013    246.2  8.286e-17         3.179         3.240 
+ m          1.770           0.011    159.3  2.695e-15         1.745         1.796 
 ",
         );
 }
@@ -65,12 +87,23 @@ fn plain() {
 fn csv() {
     cmd().arg("-o=csv").assert().success().stdout(
         "\
-Parameter,Value,Standard Error,t-value
-c,3.2099657167997013,0.013936863525869892,230.32195951702457
-m,1.7709542029456211,0.011883297834310212,149.02884936809457
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,3.2099657168276927,0.01303674207913425,246.2245319684089,0.00000000000000008286309760615685,3.179902935683619,3.2400284979717666
+m,1.7709542026534584,0.011115807266608062,159.31854162076138,0.00000000000000269522329018097,1.7453211051305277,1.796587300176389
   Number of observations: 10.0
-  Root Mean Squared Residual error: 0.043
+  Residual degrees of freedom: 8.0
+  Sum of Squared Residuals: 0.013
+  Root Mean Squared Residual error: 0.041
+  Reduced Chi-sq: 0.001
+  R-sq: 0.999
   R-sq Adjusted: 0.999
+  F-statistic: 25.38 K
+  F-statistic p-value: 2.695e-15
+  Log-likelihood: 18.84
+  AIC: -31.69
+  AICc: -27.69
+  BIC: -30.78
+  Solver: 2 iterations, 2 function evaluations, convergence in orthogonality
 ",
     );
 
@@ -81,9 +114,9 @@ m,1.7709542029456211,0.011883297834310212,149.02884936809457
         .success()
         .stdout(
             "\
-Parameter,Value,Standard Error,t-value
-c,3.2099657167997013,0.013936863525869892,230.32195951702457
-m,1.7709542029456211,0.011883297834310212,149.02884936809457
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,3.2099657168276927,0.01303674207913425,246.2245319684089,0.00000000000000008286309760615685,3.179902935683619,3.2400284979717666
+m,1.7709542026534584,0.011115807266608062,159.31854162076138,0.00000000000000269522329018097,1.7453211051305277,1.796587300176389
 ",
         );
 }
@@ -92,13 +125,24 @@ m,1.7709542029456211,0.011883297834310212,149.02884936809457
 fn md() {
     cmd().arg("-o=md").assert().success().stdout(
         "\
-| Parameter | Value | Standard Error | t-value |
-|-----------|-------|----------------|---------|
-| c         | 3.209 |          0.013 |   230.3 |
-| m         | 1.770 |          0.011 |   149.0 |
+| Parameter | Value | Standard Error | t-value | p-value   | 95% CI Lower | 95% CI Upper |
+|-----------|-------|----------------|---------|-----------|--------------|--------------|
+| c         | 3.209 |          0.013 |   246.2 | 8.286e-17 |        3.179 |        3.240 |
+| m         | 1.770 |          0.011 |   159.3 | 2.695e-15 |        1.745 |        1.796 |
   Number of observations: 10.0
-  Root Mean Squared Residual error: 0.043
+  Residual degrees of freedom: 8.0
+  Sum of Squared Residuals: 0.013
+  Root Mean Squared Residual error: 0.041
+  Reduced Chi-sq: 0.001
+  R-sq: 0.999
   R-sq Adjusted: 0.999
+  F-statistic: 25.38 K
+  F-statistic p-value: 2.695e-15
+  Log-likelihood: 18.84
+  AIC: -31.69
+  AICc: -27.69
+  BIC: -30.78
+  Solver: 2 iterations, 2 function evaluations, convergence in orthogonality
 ",
     );
 
@@ -109,10 +153,10 @@ fn md() {
         .success()
         .stdout(
             "\
-| Parameter | Value | Standard Error | t-value |
-|-----------|-------|----------------|---------|
-| c         | 3.209 |          0.013 |   230.3 |
-| m         | 1.770 |          0.011 |   149.0 |
+| Parameter | Value | Standard Error | t-value | p-value   | 95% CI Lower | 95% CI Upper |
+|-----------|-------|----------------|---------|-----------|--------------|--------------|
+| c         | 3.209 |          0.013 |   246.2 | 8.286e-17 |        3.179 |        3.240 |
+| m         | 1.770 |          0.011 |   159.3 | 2.695e-15 |        1.745 |        1.796 |
 ",
         );
 }
@@ -120,7 +164,7 @@ fn md() {
 #[test]
 fn json() {
     cmd().arg("-o=json").assert().success().stdout(
-        "{\"parameter_names\":[\"c\",\"m\"],\"parameter_values\":[3.2099657167997013,1.7709542029456211],\"n\":10,\"xerrs\":[0.013936863525869892,0.011883297834310212],\"rmsr\":0.04392493014188053,\"rsq\":0.9995948974725735,\"tvals\":[230.32195951702457,149.02884936809457]}"
+        "{\"parameter_names\":[\"c\",\"m\"],\"parameter_values\":[3.2099657168276927,1.7709542026534584],\"n\":10,\"xerrs\":[0.01303674207913425,0.011115807266608062],\"rmsr\":0.041088009832223986,\"rsq\":0.999645535288385,\"rsq_unadjusted\":0.9996849202563421,\"dfr\":8.0,\"ssr\":0.013505796415783481,\"reduced_chisq\":0.0016882245519729351,\"fstat\":25382.39770416628,\"fstat_pval\":2.69522329018097e-15,\"log_likelihood\":18.846721740703735,\"aic\":-31.69344348140747,\"aicc\":-27.69344348140747,\"bic\":-30.785688202425334,\"tvals\":[246.2245319684089,159.31854162076138],\"pvals\":[8.286309760615685e-17,2.69522329018097e-15],\"ci_level\":0.95,\"ci_lower\":[3.179902935683619,1.7453211051305277],\"ci_upper\":[3.2400284979717666,1.796587300176389],\"covariance\":[[0.00016995664403786958,-0.000011838146032650803],[-0.000011838146032650803,0.00012356117118837658]],\"correlation\":[[1.0,-0.0816908869673713],[-0.0816908869673713,1.0]],\"iterations\":2,\"function_evaluations\":2,\"termination\":\"convergence in orthogonality\"}"
     );
 
     cmd()
@@ -129,6 +173,870 @@ fn json() {
         .assert()
         .success()
         .stdout(
-        "{\"parameter_names\":[\"c\",\"m\"],\"parameter_values\":[3.2099657167997013,1.7709542029456211],\"n\":10,\"xerrs\":[0.013936863525869892,0.011883297834310212],\"rmsr\":0.04392493014188053,\"rsq\":0.9995948974725735,\"tvals\":[230.32195951702457,149.02884936809457]}"
+        "{\"parameter_names\":[\"c\",\"m\"],\"parameter_values\":[3.2099657168276927,1.7709542026534584],\"n\":10,\"xerrs\":[0.01303674207913425,0.011115807266608062],\"rmsr\":0.041088009832223986,\"rsq\":0.999645535288385,\"rsq_unadjusted\":0.9996849202563421,\"dfr\":8.0,\"ssr\":0.013505796415783481,\"reduced_chisq\":0.0016882245519729351,\"fstat\":25382.39770416628,\"fstat_pval\":2.69522329018097e-15,\"log_likelihood\":18.846721740703735,\"aic\":-31.69344348140747,\"aicc\":-27.69344348140747,\"bic\":-30.785688202425334,\"tvals\":[246.2245319684089,159.31854162076138],\"pvals\":[8.286309760615685e-17,2.69522329018097e-15],\"ci_level\":0.95,\"ci_lower\":[3.179902935683619,1.7453211051305277],\"ci_upper\":[3.2400284979717666,1.796587300176389],\"covariance\":[[0.00016995664403786958,-0.000011838146032650803],[-0.000011838146032650803,0.00012356117118837658]],\"correlation\":[[1.0,-0.0816908869673713],[-0.0816908869673713,1.0]],\"iterations\":2,\"function_evaluations\":2,\"termination\":\"convergence in orthogonality\"}"
+        );
+}
+
+#[test]
+fn numeric_derivatives() {
+    cmd()
+        .args(["-o=csv", "--no-stats", "--derivatives=numeric"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,3.2099657167997013,0.01303674209000785,246.22453176089243,0.00000000000000008286309816478217,3.179902935630553,3.24002849796885
+m,1.7709542029456211,0.011115807280245396,159.31854145158633,0.0000000000000026952233130702224,1.7453211053912427,1.7965873004999995
+",
+        );
+}
+
+#[test]
+fn fixed_parameter() {
+    cmd()
+        .args(["-o=csv", "--no-stats", "--fix", "c=3"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,3,fixed,,,,
+m,1.7855791373344618,0.06038665684099472,29.569100704417284,0.0000000002825737314453987,1.6489750290241685,1.9221832456447552
+",
+        );
+}
+
+#[test]
+fn bounded_parameter() {
+    cmd()
+        .args(["-o=csv", "--no-stats", "--bound", "m=..1.5"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,3.23592529125,0.10628034085307625,30.44707295136923,0.00000000021766783792272162,2.995502456924579,3.476348125575421
+m,1.5,0,,,,
+",
+        );
+}
+
+#[test]
+fn initial_values() {
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args([
+            "P",
+            "0.5 * (1 + tanh(((x - Mean) * sqrt(pi) * log(2)) / (Stdev^2 * sqrt(2))))",
+            "tests/norm.csv",
+            "-o=csv",
+            "--no-stats",
+            "--init=Mean=0,Stdev=1",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+Mean,0.40901556607430517,0.007435335263585774,55.00970051443959,0.00000000000000000000000000000000000007726557611628069,0.39396351675997326,0.4240676153886371
+Stdev,0.7615534220378191,0.003321931138921714,229.25021326149954,0.00000000000000000000000000000000000000000000000000000000000026760504017077897,0.7548285240272685,0.7682783200483696
+",
+        );
+}
+
+#[test]
+fn multistart() {
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args([
+            "P",
+            "0.5 * (1 + tanh(((x - Mean) * sqrt(pi) * log(2)) / (Stdev^2 * sqrt(2))))",
+            "tests/norm.csv",
+            "-o=csv",
+            "--multistart=10",
+            "--seed=1",
+            "--bound=Stdev=0..",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+Mean,0.40901556864174293,0.007435335212025884,55.00970124120331,0.00000000000000000000000000000000000007726553779500037,0.3939635194317886,0.4240676178516973
+Stdev,0.761553416895435,0.0033219311332962484,229.25021210170885,0.00000000000000000000000000000000000000000000000000000000000026760509158008456,0.7548285188962726,0.7682783148945974
+  Number of observations: 40.0
+  Residual degrees of freedom: 38.0
+  Sum of Squared Residuals: 0.003
+  Root Mean Squared Residual error: 0.008
+  Reduced Chi-sq: 7.957e-5
+  R-sq: 0.999
+  R-sq Adjusted: 0.999
+  F-statistic: 61.30 K
+  F-statistic p-value: 1.436e-62
+  Log-likelihood: 133.0
+  AIC: -260.0
+  AICc: -259.4
+  BIC: -255.0
+  Multi-start: 5 of 10 starts converged to the best minimum
+  Solver: 13 iterations, 23 function evaluations, convergence in chi-square value
+",
+        );
+}
+
+#[test]
+fn solver_settings() {
+    cmd()
+        .args(["-o=csv", "--derivatives=numeric", "--max-iter=1"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,3.2099657081257353,0.013036742083591776,246.22453121672496,0.00000000000000008286309962965816,3.1799029269713825,3.240028489280088
+m,1.7709541934331499,0.011115807208027696,159.3185416308938,0.0000000000000026952232888100655,1.7453210960453056,1.796587290820994
+  Number of observations: 10.0
+  Residual degrees of freedom: 8.0
+  Sum of Squared Residuals: 0.013
+  Root Mean Squared Residual error: 0.041
+  Reduced Chi-sq: 0.001
+  R-sq: 0.999
+  R-sq Adjusted: 0.999
+  F-statistic: 25.38 K
+  F-statistic p-value: 2.695e-15
+  Log-likelihood: 18.84
+  AIC: -31.69
+  AICc: -27.69
+  BIC: -30.78
+  Solver: 2 iterations, 5 function evaluations, maximum number of iterations reached
+",
+        );
+}
+
+#[test]
+fn weights() {
+    cmd()
+        .args(["-o=csv", "--weights=a Space col"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,3.201551115251382,0.013035112584447575,245.6097785508358,0.00000000000000008453673429357687,3.171492091728794,3.23161013877397
+m,1.7629475089029352,0.013210599142629996,133.4494741585175,0.000000000000011116828607463967,1.7324838126515059,1.7934112051543645
+  Number of observations: 10.0
+  Residual degrees of freedom: 8.0
+  Sum of Squared Residuals: 0.071
+  Root Mean Squared Residual error: 0.094
+  Reduced Chi-sq: 0.008
+  R-sq: 0.999
+  R-sq Adjusted: 0.999
+  F-statistic: 17.80 K
+  F-statistic p-value: 1.111e-14
+  Log-likelihood: 18.03
+  AIC: -30.07
+  AICc: -26.07
+  BIC: -29.16
+  Solver: 2 iterations, 2 function evaluations, convergence in orthogonality
+",
+        );
+
+    cmd()
+        .args(["-o=csv", "--no-stats", "--sigma=a Space col"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,3.240287908567735,0.010294054273554312,314.772763234027,0.000000000000000011617551855436715,3.2165497768449027,3.2640260402905676
+m,1.7741258555080655,0.006309503441497345,281.18311876014087,0.00000000000000002865140241812874,1.7595761144808877,1.7886755965352432
+",
+        );
+}
+
+#[test]
+fn robust_loss() {
+    let cmd = || {
+        let mut c = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        c.args(["y", "m * x + c", "tests/outliers.csv", "-o=csv"]);
+        c
+    };
+
+    cmd().args(["--loss=huber"]).assert().success().stdout(
+        "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,1.0401148417941886,0.16474630495663148,6.31343350655417,0.000005969361679806334,0.6939956986625303,1.386233984925847
+m,1.9970287481411633,0.029885004625588296,66.82377242904144,0.00000000000000000000005035374970486693,1.9342426832505595,2.059814813031767
+  Number of observations: 20.0
+  Residual degrees of freedom: 18.0
+  Sum of Squared Residuals: 2.434
+  Root Mean Squared Residual error: 0.367
+  Reduced Chi-sq: 0.135
+  R-sq: 0.995
+  R-sq Adjusted: 0.995
+  F-statistic: 4.465 K
+  F-statistic p-value: 5.035e-23
+  Log-likelihood: -11.61
+  AIC: 29.23
+  AICc: 30.73
+  BIC: 32.21
+  Solver: 1 iterations, 2 function evaluations, convergence in chi-square value
+  Robust loss: huber, 12 reweighting rounds
+",
+    );
+
+    cmd()
+        .args(["--loss=tukey", "--no-stats"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,1.0236626345770465,0.03632985341078323,28.176899669878892,0.00000000000000024232621650186073,0.947336444827606,1.099988824326487
+m,1.9967323448523357,0.006577411033024674,303.5742079713274,0.00000000000000000000000000000000007660104932368205,1.9829137170453297,2.010550972659342
+",
+        );
+}
+
+#[test]
+fn confidence_level() {
+    cmd()
+        .args(["-o=md", "--no-stats", "--confidence=0.99"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+| Parameter | Value | Standard Error | t-value | p-value   | 99% CI Lower | 99% CI Upper |
+|-----------|-------|----------------|---------|-----------|--------------|--------------|
+| c         | 3.209 |          0.013 |   246.2 | 8.286e-17 |        3.166 |        3.253 |
+| m         | 1.770 |          0.011 |   159.3 | 2.695e-15 |        1.733 |        1.808 |
+",
+        );
+}
+
+#[test]
+fn covariance() {
+    cmd()
+        .args(["-o=csv", "--no-stats", "--covariance"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,3.2099657168276927,0.01303674207913425,246.2245319684089,0.00000000000000008286309760615685,3.179902935683619,3.2400284979717666
+m,1.7709542026534584,0.011115807266608062,159.31854162076138,0.00000000000000269522329018097,1.7453211051305277,1.796587300176389
+
+Covariance,c,m
+c,0.00016995664403786958,-0.000011838146032650803
+m,-0.000011838146032650803,0.00012356117118837658
+
+Correlation,c,m
+c,1,-0.0816908869673713
+m,-0.0816908869673713,1
+",
+        );
+
+    cmd()
+        .args(["-o=md", "--no-stats", "--covariance"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+| Parameter | Value | Standard Error | t-value | p-value   | 95% CI Lower | 95% CI Upper |
+|-----------|-------|----------------|---------|-----------|--------------|--------------|
+| c         | 3.209 |          0.013 |   246.2 | 8.286e-17 |        3.179 |        3.240 |
+| m         | 1.770 |          0.011 |   159.3 | 2.695e-15 |        1.745 |        1.796 |
+
+| Covariance | c         | m         |
+|------------|-----------|-----------|
+| c          |  1.699e-4 | -1.183e-5 |
+| m          | -1.183e-5 |  1.235e-4 |
+
+| Correlation | c      | m      |
+|-------------|--------|--------|
+| c           |    1.0 | -0.081 |
+| m           | -0.081 |    1.0 |
+",
+        );
+}
+
+#[test]
+fn residuals() {
+    let dir = std::env::temp_dir();
+    let csv = dir.join("fitme-residuals-test.csv");
+    let json = dir.join("fitme-residuals-test.json");
+
+    cmd()
+        .args(["-o=csv", "--no-stats", "--residuals"])
+        .arg(&csv)
+        .assert()
+        .success();
+
+    let s = std::fs::read_to_string(&csv).unwrap();
+    let mut lines = s.lines();
+    assert_eq!(
+        lines.next(),
+        Some("y,x,a Space col,predicted,residual,standardised_residual,leverage")
+    );
+    assert_eq!(
+        lines.next(),
+        Some("0.19000429,-1.7237128,1,0.15734928950013272,0.032655000499867276,0.9799929633674834,0.3423069344682138")
+    );
+    assert_eq!(lines.count(), 9);
+
+    cmd()
+        .args(["-o=csv", "--no-stats", "--residuals"])
+        .arg(&json)
+        .assert()
+        .success();
+
+    let s = std::fs::read_to_string(&json).unwrap();
+    let rows: Vec<serde_json::Value> = serde_json::from_str(&s).unwrap();
+    assert_eq!(rows.len(), 10);
+    assert_eq!(rows[0]["a Space col"], 1.0);
+    assert_eq!(rows[0]["leverage"], 0.3423069344682138);
+    let h = rows
+        .iter()
+        .map(|r| r["leverage"].as_f64().unwrap())
+        .sum::<f64>();
+    assert!((h - 2.0).abs() < 1e-9);
+
+    std::fs::remove_file(csv).ok();
+    std::fs::remove_file(json).ok();
+}
+
+#[test]
+fn predict() {
+    let predict = || {
+        let mut c = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        c.args(["predict", "tests/file1-fit.json", "--expr", "m * x + c"])
+            .write_stdin("x\n0\n1.5\n");
+        c
+    };
+
+    predict().assert().success().stdout(
+        "\
+x,predicted
+0,3.2099657168276927
+1.5,5.866397020807881
+",
+    );
+
+    predict().arg("--bands").assert().success().stdout(
+        "\
+x,predicted,standard_error,confidence_lower,confidence_upper,prediction_lower,prediction_upper
+0,3.2099657168276927,0.013936863514245524,3.1778272519320665,3.242104181723319,3.1036983093212163,3.316233124334169
+1.5,5.866397020807881,0.021711217406513016,5.816330863688145,5.916463177927617,5.753408078600963,5.979385963014798
+",
+    );
+
+    predict()
+        .args(["--bands", "--confidence=0.5", "-o=json"])
+        .assert()
+        .success()
+        .stdout(r#"[{"x":0.0,"predicted":3.2099657168276927,"standard_error":0.013936863514245524,"confidence_lower":3.2001209030189712,"confidence_upper":3.219810530636414,"prediction_lower":3.177413358019194,"prediction_upper":3.2425180756361915},{"x":1.5,"predicted":5.866397020807881,"standard_error":0.021711217406513016,"confidence_lower":5.8510605074876985,"confidence_upper":5.881733534128063,"prediction_lower":5.831785688185617,"prediction_upper":5.901008353430145}]"#);
+}
+
+#[test]
+fn target_named_predict() {
+    // `predict` as the first argument is the command
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args(["-o=csv", "-n", "--", "predict", "m * x + c"])
+        .write_stdin("x,predict\n0,1\n1,3\n2,5.1\n3,6.9\n")
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,1.0299999999999985,0.0793725393319376,12.97678023998347,0.005885976294192167,0.6884875269762907,1.3715124730237063
+m,1.980000000000001,0.042426406871192784,46.669047558312236,0.00045882085461859017,1.7974539046622022,2.1625460953378
+",
+        );
+}
+
+#[test]
+fn save_model() {
+    let path = std::env::temp_dir().join("fitme-save-model-test.json");
+
+    cmd()
+        .args(["-o=csv", "--no-stats", "--fix=c=3.2", "--save-model"])
+        .arg(&path)
+        .assert()
+        .success();
+
+    let model: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(model["version"], 1);
+    assert_eq!(model["expr"], "m * x + c");
+    assert_eq!(model["target"], "y");
+    assert_eq!(model["eq_resolver"], "v1");
+    assert_eq!(model["options"]["fixed"][0]["name"], "c");
+    assert_eq!(model["parameter_names"], serde_json::json!(["c", "m"]));
+
+    // a model file can be used for predictions, with its stored equation
+    let predict = || {
+        let mut c = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        c.arg("predict").arg(&path).write_stdin("x\n0\n");
+        c
+    };
+    predict().assert().success().stdout("x,predicted\n0,3.2\n");
+    predict()
+        .args(["--expr", "m*x + c", "--eq-resolver", "v1"])
+        .assert()
+        .success()
+        .stdout("x,predicted\n0,3.2\n");
+    predict()
+        .args(["--expr", "m * x"])
+        .assert()
+        .failure()
+        .stderr("Error:   × equation 'm * x' does not match the model's equation 'm * x + c'\n\n");
+
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn compare() {
+    cmd()
+        .args(["--compare", "a * x^2 + m * x + c", "--compare", "c"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+───────────────────────────────────────────────────────────────────────────────────────────
+ Rank   Expression            Parameters   AIC      ΔAIC    BIC      R-sq Adjusted   RMSR  
+═══════════════════════════════════════════════════════════════════════════════════════════
+ 1      a * x^2 + m * x + c            3   -32.20       0   -30.99           0.999   0.038 
+───────────────────────────────────────────────────────────────────────────────────────────
+ 2      m * x + c                      2   -31.69   0.510   -30.78           0.999   0.041 
+───────────────────────────────────────────────────────────────────────────────────────────
+ 3      c                              1    46.93   79.13    47.53               0   2.182 
+───────────────────────────────────────────────────────────────────────────────────────────
+
+──────────────────────────────────────────────────────────────────────────────
+ Restricted   Full                  F-statistic   df num   df den   p-value   
+══════════════════════════════════════════════════════════════════════════════
+ m * x + c    a * x^2 + m * x + c         1.997      1.0      7.0       0.200 
+──────────────────────────────────────────────────────────────────────────────
+ c            m * x + c                 25.38 K      1.0      8.0   2.695e-15 
+──────────────────────────────────────────────────────────────────────────────
+ c            a * x^2 + m * x + c       14.27 K      2.0      7.0   2.306e-13 
+──────────────────────────────────────────────────────────────────────────────
+",
+        );
+
+    cmd()
+        .args(["--compare-file", "tests/exprs.txt", "-o=csv"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Rank,Expression,Parameters,AIC,ΔAIC,BIC,R-sq Adjusted,RMSR
+1,a * x^2 + m * x + c,3,-32.20344889403048,0,-30.993108522054296,0.9996848213413775,0.03874422734830404
+2,m * x + c,2,-31.69344348140747,0.5100054126230091,-30.785688202425334,0.999645535288385,0.041088009832223986
+3,c,1,46.933404485666614,79.1368533796971,47.53857467165471,0,2.1823711057402875
+
+Restricted,Full,F-statistic,df num,df den,p-value
+m * x + c,a * x^2 + m * x + c,1.9971754601489466,1,7,0.20048173860450902
+c,m * x + c,25382.39770416628,1,8,0.00000000000000269522329018097
+c,a * x^2 + m * x + c,14274.11644670919,2,7,0.00000000000023064453412403374
+",
+        );
+}
+
+#[test]
+fn bootstrap() {
+    cmd()
+        .args(["-o=csv", "--bootstrap=200", "--seed=1"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,3.2099657168276927,0.01303674207913425,246.2245319684089,0.00000000000000008286309760615685,3.179902935683619,3.2400284979717666
+m,1.7709542026534584,0.011115807266608062,159.31854162076138,0.00000000000000269522329018097,1.7453211051305277,1.796587300176389
+
+Parameter,Bootstrap SE,95% CI Lower,95% CI Upper
+c,0.011691817433615383,3.1860822654938667,3.2294690983162124
+m,0.011856941138652693,1.745097654880903,1.7885874168504294
+  Number of observations: 10.0
+  Residual degrees of freedom: 8.0
+  Sum of Squared Residuals: 0.013
+  Root Mean Squared Residual error: 0.041
+  Reduced Chi-sq: 0.001
+  R-sq: 0.999
+  R-sq Adjusted: 0.999
+  F-statistic: 25.38 K
+  F-statistic p-value: 2.695e-15
+  Log-likelihood: 18.84
+  AIC: -31.69
+  AICc: -27.69
+  BIC: -30.78
+  Solver: 2 iterations, 2 function evaluations, convergence in orthogonality
+  Bootstrap: 200 resamples of rows, 0 failed
+",
+        );
+
+    cmd()
+        .args([
+            "-o=md",
+            "-n",
+            "--bootstrap=200",
+            "--seed=1",
+            "--resample=residuals",
+            "--confidence=0.9",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "\
+| Parameter | Value | Standard Error | t-value | p-value   | 90% CI Lower | 90% CI Upper |
+|-----------|-------|----------------|---------|-----------|--------------|--------------|
+| c         | 3.209 |          0.013 |   246.2 | 8.286e-17 |        3.185 |        3.234 |
+| m         | 1.770 |          0.011 |   159.3 | 2.695e-15 |        1.750 |        1.791 |
+
+| Parameter | Bootstrap SE | 90% CI Lower | 90% CI Upper |
+|-----------|--------------|--------------|--------------|
+| c         |        0.011 |        3.189 |        3.227 |
+| m         |        0.009 |        1.754 |        1.786 |
+",
+        );
+}
+
+#[test]
+fn cross_validation() {
+    cmd()
+        .args(["-o=csv", "--cv=5", "--seed=1"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Fold,Observations,RMSE,R-sq,c,m
+1,2,0.029347342949266168,0.9994980295335745,3.2045386043285986,1.7700304544829124
+2,2,0.04550690131530197,0.9974481924171301,3.219266408984905,1.7665981862805624
+3,2,0.018629528379365035,0.9996309395260699,3.206321550395084,1.7727605770054113
+4,2,0.06069286419914639,0.9838717311011659,3.202277152532293,1.7596227749601618
+5,2,0.06681059212388347,0.9990792825295108,3.2120701065852457,1.7817880526326257
+  Folds: 5
+  Out-of-sample RMSE: 0.044
+  Out-of-sample RMSE std dev: 0.020
+  Out-of-sample R-sq: 0.995
+  Out-of-sample R-sq std dev: 0.006
+",
+        );
+
+    cmd()
+        .args(["-o=csv", "--cv=5", "--seed=1", "-n"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Fold,Observations,RMSE,R-sq,c,m
+1,2,0.029347342949266168,0.9994980295335745,3.2045386043285986,1.7700304544829124
+2,2,0.04550690131530197,0.9974481924171301,3.219266408984905,1.7665981862805624
+3,2,0.018629528379365035,0.9996309395260699,3.206321550395084,1.7727605770054113
+4,2,0.06069286419914639,0.9838717311011659,3.202277152532293,1.7596227749601618
+5,2,0.06681059212388347,0.9990792825295108,3.2120701065852457,1.7817880526326257
+",
+        );
+}
+
+#[test]
+fn group_by() {
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args([
+            "sepalLength",
+            "a * petalLength + b",
+            "tests/iris.csv",
+            "--group-by=class",
+            "-o=md",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "\
+| class           | Parameter | Value | Standard Error | t-value | p-value   | 95% CI Lower | 95% CI Upper |
+|-----------------|-----------|-------|----------------|---------|-----------|--------------|--------------|
+| Iris-setosa     | a         | 0.536 |          0.282 |   1.895 |     0.064 |       -0.032 |        1.104 |
+| Iris-setosa     | b         | 4.221 |          0.416 |   10.12 | 1.683e-13 |        3.382 |        5.059 |
+| Iris-versicolor | a         | 0.828 |          0.104 |   7.953 | 2.586e-10 |        0.618 |        1.037 |
+| Iris-versicolor | b         | 2.407 |          0.446 |   5.394 |  2.075e-6 |        1.510 |        3.304 |
+| Iris-virginica  | a         | 0.995 |          0.083 |   11.90 | 6.297e-16 |        0.827 |        1.163 |
+| Iris-virginica  | b         | 1.059 |          0.466 |   2.270 |     0.027 |        0.121 |        1.998 |
+
+| class           | Observations | RMSR  | R-sq  | R-sq Adjusted | AIC   | BIC   |
+|-----------------|--------------|-------|-------|---------------|-------|-------|
+| Iris-setosa     |           50 | 0.343 | 0.069 |         0.050 | 39.00 | 44.73 |
+| Iris-versicolor |           50 | 0.342 | 0.568 |         0.559 | 38.71 | 44.45 |
+| Iris-virginica  |           50 | 0.323 | 0.746 |         0.741 | 32.91 | 38.64 |
+",
+        );
+}
+
+#[test]
+fn categorical() {
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args([
+            "sepalLength",
+            "a * class + b * petalLength + c",
+            "tests/iris.csv",
+            "--categorical=class",
+            "-o=csv",
+            "-n",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+a[Iris-versicolor],-1.5983153563555952,0.19345163499965354,-8.262092777651931,0.00000000000007944590753575548,-1.980642654561228,-1.2159880581499625
+a[Iris-virginica],-2.114621307861832,0.2734875457532101,-7.7320570559729465,0.0000000000015817506696345,-2.655127230496714,-1.5741153852269503
+b,0.9042615723732469,0.06482177461738335,13.949966314725819,0.00000000000000000000000000012082301335526853,0.7761513397851221,1.0323718049613717
+c,3.682161058045564,0.10627007546112299,34.649086697906945,0.0000000000000000000000000000000000000000000000000000000000000000000000025579089886048247,3.472134656194618,3.8921874598965105
+",
+        );
+}
+
+#[test]
+fn categorical_levels_persist() {
+    let path = std::env::temp_dir().join("fitme-categorical-test.json");
+
+    // the filtered out level is not a level of the fit
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args([
+            "sepalLength",
+            "a * class + b * petalLength + c",
+            "tests/iris.csv",
+            "--categorical=class",
+            "--where=class != Iris-setosa",
+            "-o=csv",
+            "--no-stats",
+            "--save-model",
+        ])
+        .arg(&path)
+        .assert()
+        .success();
+
+    let model: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(
+        model["levels"]["class"],
+        serde_json::json!(["Iris-versicolor", "Iris-virginica"])
+    );
+
+    // predictions bind to the fitted levels, even if the data has a subset of them
+    let predict = |csv: &str| {
+        let mut c = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        c.arg("predict").arg(&path).write_stdin(csv);
+        c
+    };
+    predict("petalLength,class\n5,Iris-virginica\n")
+        .assert()
+        .success()
+        .stdout("petalLength,class,predicted\n5,Iris-virginica,6.077201454274209\n");
+    predict("petalLength,class\n5,Iris-setosa\n")
+        .assert()
+        .failure()
+        .stderr(
+            "\
+Reading CSV from stdin
+Error:   × from stdin
+  ├─▶ in row index 1
+  ╰─▶ 'Iris-setosa' is not a level of categorical column 'class', expecting
+      one of: Iris-versicolor, Iris-virginica
+
+",
+        );
+
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn filter_rows() {
+    cmd()
+        .args(["-o=csv", "--where", "x > 0"])
+        .assert()
+        .success()
+        .stderr("Filtered out 5 of 10 rows\n")
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,3.1512528183032065,0.036737201579933314,85.77824882623862,0.00000349242245834855,3.034338646868685,3.268166989737728
+m,1.8158854413325276,0.029283157638502016,62.011257930222975,0.00000923958982124768,1.7226933644988365,1.9090775181662187
+  Number of observations: 5.0
+  Residual degrees of freedom: 3.0
+  Sum of Squared Residuals: 0.004
+  Root Mean Squared Residual error: 0.040
+  Reduced Chi-sq: 0.001
+  R-sq: 0.999
+  R-sq Adjusted: 0.998
+  F-statistic: 3.845 K
+  F-statistic p-value: 9.239e-6
+  Log-likelihood: 10.19
+  AIC: -14.39
+  AICc: 9.602
+  BIC: -15.56
+  Solver: 2 iterations, 2 function evaluations, convergence in orthogonality
+",
+        );
+}
+
+#[test]
+fn derived_columns() {
+    let csv = std::env::temp_dir().join("fitme-derived-test.csv");
+    let model = std::env::temp_dir().join("fitme-derived-model-test.json");
+
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args([
+            "y",
+            "a * x2 + m * x + c",
+            "tests/file1.csv",
+            "--let",
+            "x2 = x^2",
+            "-o=csv",
+            "-n",
+            "--where",
+            "x2 >= 0",
+            "--save-model",
+        ])
+        .arg(&model)
+        .arg("--residuals")
+        .arg(&csv)
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+a,0.014902027226385653,0.010544773169022763,1.4132145839004993,0.2004817386044968,-0.010032399136630439,0.039836453589401744
+c,3.189696326684756,0.018890066505569954,168.85574890613736,0.00000000000006742680265429495,3.1450284173114844,3.234364236058027
+m,1.7685730061937854,0.010616293622123884,166.59043816461121,0.00000000000007411121812578857,1.7434694608328816,1.7936765515546893
+",
+        );
+
+    let s = std::fs::read_to_string(&csv).unwrap();
+    assert_eq!(
+        s.lines().next(),
+        Some("y,x,a Space col,x2,predicted,residual,standardised_residual,leverage")
+    );
+
+    // a model stores its definitions and filters, and applies the definitions when predicting
+    let m: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&model).unwrap()).unwrap();
+    assert_eq!(m["derived"], serde_json::json!(["x2 = x^2"]));
+    assert_eq!(m["filters"], serde_json::json!(["x2 >= 0"]));
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .arg("predict")
+        .arg(&model)
+        .write_stdin("x\n2\n")
+        .assert()
+        .success()
+        .stdout("x,x2,predicted\n2,4,6.786450447977869\n");
+
+    std::fs::remove_file(model).ok();
+}
+
+#[test]
+fn debug_missing_values() {
+    // debugging does not apply the missing value policy
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args([
+            "y",
+            "m * x2 + c",
+            "tests/missing.csv",
+            "--let",
+            "x2 = x^2",
+            "--debug",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "\
+✖️ Expression:
+  m * x2 + c
+📊 Parameters:
+  c
+  m
+🧮 Variables:
+  x2
+🔎 Target:
+  y
+",
+        );
+}
+
+#[test]
+fn missing_values() {
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args(["y", "m * x + c", "tests/missing.csv", "-o=csv", "--missing", "drop"])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,3.2255639097744355,0.01323346243854045,243.74300563852967,0.0000000000003218071374135656,3.1931827937018893,3.2579450258469818
+m,1.7651629072681705,0.005241232127437975,336.78395925788146,0.00000000000004625248583108716,1.7523380742609407,1.7779877402754003
+  Number of observations: 8.0
+  Dropped rows with missing values: 2
+  Residual degrees of freedom: 6.0
+  Sum of Squared Residuals: 0.008
+  Root Mean Squared Residual error: 0.037
+  Reduced Chi-sq: 0.001
+  R-sq: 0.999
+  R-sq Adjusted: 0.999
+  F-statistic: 113.4 K
+  F-statistic p-value: 4.625e-14
+  Log-likelihood: 16.17
+  AIC: -26.34
+  AICc: -20.34
+  BIC: -26.10
+  Solver: 2 iterations, 2 function evaluations, convergence in orthogonality
+",
+        );
+}
+
+#[test]
+fn csv_dialect() {
+    Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args([
+            "c1",
+            "m * c0 + c",
+            "tests/dialect.csv",
+            "--delimiter=;",
+            "--quote='",
+            "--comment=#",
+            "--no-header",
+            "--skip-rows=2",
+            "-o=csv",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "\
+Parameter,Value,Standard Error,t-value,p-value,95% CI Lower,95% CI Upper
+c,3.2099657168276927,0.01303674207913425,246.2245319684089,0.00000000000000008286309760615685,3.179902935683619,3.2400284979717666
+m,1.7709542026534584,0.011115807266608062,159.31854162076138,0.00000000000000269522329018097,1.7453211051305277,1.796587300176389
+  Number of observations: 10.0
+  Residual degrees of freedom: 8.0
+  Sum of Squared Residuals: 0.013
+  Root Mean Squared Residual error: 0.041
+  Reduced Chi-sq: 0.001
+  R-sq: 0.999
+  R-sq Adjusted: 0.999
+  F-statistic: 25.38 K
+  F-statistic p-value: 2.695e-15
+  Log-likelihood: 18.84
+  AIC: -31.69
+  AICc: -27.69
+  BIC: -30.78
+  Solver: 2 iterations, 2 function evaluations, convergence in orthogonality
+",
         );
 }